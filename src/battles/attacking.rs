@@ -1,30 +1,79 @@
-use rand::{thread_rng, Rng};
+use crate::{cardparse::enums::{AttackType, Ability}, gamedata::{monster::Monster, statuseffect::StatusEffect}};
 
-use crate::{cardparse::enums::{AttackType, Ability}, gamedata::monster::Monster};
+use super::{chance::Chance, battledata::BattleData, ruleset::Ruleset, battleevent::BattleEvent, monsterkey::MonsterKey};
 
-use super::{battledata::BattleData, monsterkey::MonsterKey};
+/// Chance that a hit from a monster with Stun stuns its target
+pub const STUN_CHANCE: f32 = 0.5;
 
-pub fn print_attack(bd: &BattleData, mk: &MonsterKey, tk: &MonsterKey) {
-    let monster = bd.get(mk).expect("mk not in bd");
-    let target = bd.get(tk).expect("mk not in bd");
-    println!("{} => -{} => {}", monster, monster.get_damage(), target);
+/// Chance that a monster with Retaliate strikes back at a melee attacker
+pub const RETALIATE_CHANCE: f32 = 0.5;
+
+/// Damage Thorns does back to a melee attacker
+pub const THORNS_DAMAGE: i32 = 2;
+
+/// Attacks tk with mk's first attack
+pub fn attack(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, rng: &mut dyn Chance) -> bool {
+    let attack_type = bd.get(mk).expect("mk not in bd").get_attack_type();
+    attack_with(bd, mk, tk, attack_type, rng)
 }
 
-pub fn attack(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey) -> bool {
+/// Strikes tk, lets the target react, then applies Dispel, Blast, Stun and Trample if the strike hit
+pub fn attack_with(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> bool {
+    // neighbours and position before the target can die
+    let neighbours = bd.enemies(mk).neighbours(tk);
+    let pos = bd.get_pos(tk);
+    let health_before = bd.get(tk).expect("tk not in bd").get_health();
+
+    if !strike_with(bd, mk, tk, attack_type, rng) {
+        return false;
+    }
+
+    let health_lost = health_before - bd.get(tk).expect("tk not in bd").get_health();
+    after_hit(bd, mk, tk, attack_type, health_lost, rng);
+
     let monster = bd.get(mk).expect("mk not in bd");
-    let res = match monster.get_attack_type() {
-        AttackType::Melee => attack_melee_or_ranged(bd, mk, tk),
-        AttackType::Ranged => attack_melee_or_ranged(bd, mk, tk),
-        AttackType::Magic => attack_magic(bd, mk, tk),
+    if !monster.is_alive() {
+        return true;
+    }
+    let has_dispel = monster.has_ability(Ability::Dispel);
+    let has_blast = monster.has_ability(Ability::Blast);
+    let has_stun = monster.has_ability(Ability::Stun);
+    let has_trample = monster.has_ability(Ability::Trample);
+
+    if has_dispel {
+        dispel(bd, mk, tk);
+    }
+    if has_blast {
+        blast(bd, mk, neighbours.as_slice(), attack_type);
+    }
+    if has_stun {
+        stun(bd, mk, tk, rng);
+    }
+    if has_trample {
+        if let Some(pos) = pos {
+            trample(bd, mk, tk, pos as usize, attack_type, rng);
+        }
+    }
+    true
+}
+
+/// A single hit on tk with no follow ups
+pub fn strike_with(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> bool {
+    match attack_type {
+        AttackType::Melee => attack_melee_or_ranged(bd, mk, tk, attack_type, rng),
+        AttackType::Ranged => attack_melee_or_ranged(bd, mk, tk, attack_type, rng),
+        AttackType::Magic => attack_magic(bd, mk, tk, rng),
         AttackType::None => false,
-    };
-    // print_attack(bd, mk, tk);
-    res
+    }
 }
 
-pub fn evade_check(monster: &Monster, target: &Monster) -> bool {
+/// Chance that target evades an attack from monster
+pub fn evade_chance(monster: &Monster, target: &Monster, reverse_speed: bool) -> f32 {
     let mut evade_chance: f32 = 0.0;
-    let speed_diff = target.get_speed() - monster.get_speed();
+    let mut speed_diff = target.get_speed() - monster.get_speed();
+    if reverse_speed {
+        speed_diff = -speed_diff;
+    }
     if speed_diff > 0 {
         evade_chance += 0.1 * speed_diff as f32;
     }
@@ -40,62 +89,214 @@ pub fn evade_check(monster: &Monster, target: &Monster) -> bool {
     if monster.has_ability(Ability::TrueStrike) {
         evade_chance = 0.0;
     }
+    evade_chance
+}
 
-    let random: f32 = thread_rng().gen();
-    if random < evade_chance {
-        return true
+pub fn evade_check(monster: &Monster, target: &Monster, reverse_speed: bool, rng: &mut dyn Chance) -> bool {
+    rng.roll(evade_chance(monster, target, reverse_speed))
+}
+
+/// Shield and Void halve damage rounded up, 1 damage is blocked entirely
+pub fn halve_damage(damage: i32) -> i32 {
+    if damage <= 1 {
+        return 0;
     }
-    false
+    (damage as f32 / 2.0).ceil() as i32
 }
 
-pub fn attack_melee_or_ranged(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey) -> bool {
+pub fn attack_melee_or_ranged(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> bool {
     let monster = bd.get(mk).expect("mk not in bd");
     let target = bd.get(tk).expect("tk not in bd");
-    let mut damage = monster.get_damage();
+    let mut damage = monster.get_damage_for(attack_type);
 
     if target.has_ability(Ability::Shield) {
-        if damage <= 1 {
-            damage = 0
-        } else {
-            let step1 = damage as f32 / 2.0;
-            damage = step1.ceil() as i32;
-        }
+        damage = halve_damage(damage);
     }
 
-    if evade_check(monster, target) || damage <= 0 {
+    if damage <= 0 {
+        return false;
+    }
+    if evade_check(monster, target, bd.has_ruleset(Ruleset::ReverseSpeed), rng) {
+        bd.emit(BattleEvent::Miss { mk: *mk, tk: *tk });
         return false;
     }
     bd.deal_damage(tk, damage);
     true
 }
 
-pub fn attack_magic(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey) -> bool {
+pub fn attack_magic(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, rng: &mut dyn Chance) -> bool {
     let monster = bd.get(mk).expect("mk not in bd");
     let target = bd.get(tk).expect("tk not in bd");
-    let mut damage = monster.get_damage();
+    let mut damage = monster.get_damage_for(AttackType::Magic);
 
     if target.has_ability(Ability::Void) {
-        if damage <= 1 {
-            damage = 0
-        } else {
-            let step1 = damage as f32 / 2.0;
-            damage = step1.ceil() as i32;
-        }
+        damage = halve_damage(damage);
     }
 
-    if evade_check(monster, target) || damage <= 0 {
+    if damage <= 0 {
+        return false;
+    }
+    if evade_check(monster, target, bd.has_ruleset(Ruleset::ReverseSpeed), rng) {
+        bd.emit(BattleEvent::Miss { mk: *mk, tk: *tk });
         return false;
     }
     bd.deal_true_damage(tk, damage);
     true
 }
 
+/// Reactions to a landed hit, health_lost is the health the target lost to it
+pub fn after_hit(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, attack_type: AttackType, health_lost: i32, rng: &mut dyn Chance) {
+    if bd.get(mk).expect("mk not in bd").has_ability(Ability::LifeLeech) {
+        life_leech(bd, mk, health_lost);
+    }
+
+    let target = bd.get(tk).expect("tk not in bd");
+    let has_thorns = target.has_ability(Ability::Thorns);
+    let has_reflect = target.has_ability(Ability::MagicReflect);
+    let has_retaliate = target.has_ability(Ability::Retaliate);
+
+    match attack_type {
+        AttackType::Melee => {
+            if has_thorns {
+                thorns(bd, tk, mk);
+            }
+            if has_retaliate {
+                retaliate(bd, tk, mk, rng);
+            }
+        },
+        AttackType::Magic if has_reflect => magic_reflect(bd, tk, mk, health_lost),
+        _ => (),
+    }
+}
+
+/// Attacker gains half the health it took from the target, rounded up, as health and max health
+pub fn life_leech(bd: &mut BattleData, mk: &MonsterKey, health_lost: i32) {
+    let amount = (health_lost as f32 / 2.0).ceil() as i32;
+    if amount <= 0 {
+        return;
+    }
+    bd.get_mut(mk).expect("mk not in bd").gain_max_health(amount);
+    bd.emit(BattleEvent::LifeLeech { mk: *mk, amount });
+}
+
+/// mk's thorns damage the melee attacker tk, through shield and armor
+pub fn thorns(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey) {
+    let attacker = bd.get(tk).expect("tk not in bd");
+    if !attacker.is_alive() {
+        return;
+    }
+    let mut damage = THORNS_DAMAGE;
+    if attacker.has_ability(Ability::Shield) {
+        damage = halve_damage(damage);
+    }
+    bd.emit(BattleEvent::Thorns { mk: *mk, tk: *tk });
+    if damage > 0 {
+        bd.deal_damage(tk, damage);
+    }
+}
+
+/// mk sends half of the health_lost to tk's magic back at it, rounded up and halved again by void
+pub fn magic_reflect(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, health_lost: i32) {
+    let attacker = bd.get(tk).expect("tk not in bd");
+    if !attacker.is_alive() {
+        return;
+    }
+    let mut damage = (health_lost as f32 / 2.0).ceil() as i32;
+    if attacker.has_ability(Ability::Void) {
+        damage = halve_damage(damage);
+    }
+    bd.emit(BattleEvent::MagicReflect { mk: *mk, tk: *tk });
+    if damage > 0 {
+        bd.deal_true_damage(tk, damage);
+    }
+}
+
+/// Chance for a surviving mk to strike back at the melee attacker tk with its melee attack, if it has one
+pub fn retaliate(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, rng: &mut dyn Chance) {
+    let monster = bd.get(mk).expect("mk not in bd");
+    if !monster.is_alive() || !monster.has_attack_type(AttackType::Melee) || !bd.get(tk).expect("tk not in bd").is_alive() {
+        return;
+    }
+    if !rng.roll(RETALIATE_CHANCE) {
+        return;
+    }
+    bd.emit(BattleEvent::Retaliate { mk: *mk, tk: *tk });
+    strike_with(bd, mk, tk, AttackType::Melee, rng);
+}
+
+/// Removes the positive effects on a target that survived the hit
+pub fn dispel(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey) {
+    let target = bd.get_mut(tk).expect("tk not in bd");
+    if !target.is_alive() || target.dispel().is_empty() {
+        return;
+    }
+    bd.emit(BattleEvent::Dispel { mk: *mk, tk: *tk });
+}
+
+/// Blast hits for half damage rounded up
+pub fn blast_damage(damage: i32) -> i32 {
+    (damage as f32 / 2.0).ceil() as i32
+}
+
+/// Splash damage to the alive monsters that were next to the target, can't be evaded but each
+/// neighbour halves it with shield against melee and ranged or void against magic
+pub fn blast(bd: &mut BattleData, mk: &MonsterKey, neighbours: &[MonsterKey], attack_type: AttackType) {
+    let monster = bd.get(mk).expect("mk not in bd");
+    let blast = blast_damage(monster.get_damage_for(attack_type));
+    for nk in neighbours {
+        let neighbour = bd.get(nk).expect("nk not in bd");
+        if !neighbour.is_alive() {
+            continue;
+        }
+        let halved_by = match attack_type {
+            AttackType::Magic => Ability::Void,
+            _ => Ability::Shield,
+        };
+        let damage = if neighbour.has_ability(halved_by) { halve_damage(blast) } else { blast };
+        bd.emit(BattleEvent::Blast { mk: *mk, tk: *nk });
+        if damage <= 0 {
+            continue;
+        }
+        match attack_type {
+            AttackType::Magic => bd.deal_true_damage(nk, damage),
+            _ => bd.deal_damage(nk, damage),
+        }
+    }
+}
+
+/// Chance for a target that survived the hit to lose its next turn
+pub fn stun(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, rng: &mut dyn Chance) {
+    let target = bd.get(tk).expect("tk not in bd");
+    if !target.is_alive() || target.has_effect(Ability::Stun) {
+        return;
+    }
+    if !rng.roll(STUN_CHANCE) {
+        return;
+    }
+    bd.add_effect(tk, StatusEffect::stun(*mk));
+    bd.emit(BattleEvent::Stun { mk: *mk, tk: *tk });
+}
+
+/// After a kill, attacks the enemy that moved into the dead target's position
+pub fn trample(bd: &mut BattleData, mk: &MonsterKey, tk: &MonsterKey, pos: usize, attack_type: AttackType, rng: &mut dyn Chance) {
+    if bd.get(tk).expect("tk not in bd").is_alive() {
+        return;
+    }
+    let enemies = bd.enemies(mk);
+    if pos >= enemies.len() {
+        return;
+    }
+    let next = enemies.index(pos);
+    bd.emit(BattleEvent::Trample { mk: *mk, tk: next });
+    attack_with(bd, mk, &next, attack_type, rng);
+}
+
 #[cfg(test)]
 mod tests {
 
     #[test]
     fn test_f32_ceil() {
-        let step = (4.0 as f32 / 2.0 as f32).ceil();
+        let step = (4.0_f32 / 2.0).ceil();
         assert_eq!(step as i32, 2);
     }
 }
@@ -1,59 +1,316 @@
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::VecDeque;
 
-use crate::{battles::{battledata::BattleData, targeting, attacking}, gamedata::{summoner::Summoner, monster::Monster, registry::Registry}, cardparse::enums::Outcome};
+use rand::{rngs::StdRng, SeedableRng};
 
-use super::{roundrobin::RoundRobin, monsterspeed::MonsterSpeed, roundrobiniter::RoundRobinIter, monsterkey::MonsterKey};
+use crate::{battles::{battledata::BattleData, attacking, healing}, gamedata::{deck::Deck, monster::Monster, statuseffect::Tick}, cardparse::enums::{Ability, Outcome}};
+
+use super::{battleconfig::BattleConfig, ruleset::{Ruleset, MeleeMayhemTargeting}, targetingpolicy::{TargetingPolicy, DefaultTargeting}, battleevent::{BattleEvent, EventSink}, chance::Chance, monsterspeed::MonsterSpeed, roundrobiniter::{RoundRobinIter, MAX_TURNS}, monsterkey::MonsterKey};
 
 
 
 #[derive(Debug)]
-pub struct Battle<'a> {
-    pub battledata: BattleData<'a>,
+pub struct Battle {
+    pub battledata: BattleData,
+    pub rng: StdRng,
+    pub round_num: u32,
+    pub config: BattleConfig,
+    pub targeting: Box<dyn TargetingPolicy>,
+    /// Monsters still to take their turn this round, in order
+    pub queue: VecDeque<MonsterKey>,
+    /// No attack has landed yet this round
+    pub round_stalled: bool,
+    /// Between start_round and end_round
+    pub in_round: bool,
+}
+
+/// Where a battle is between steps, everything that changes as it plays except the rng
+/// Equal states play out the same, so they can be merged when enumerating outcomes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BattleState {
+    pub battledata: BattleData,
+    pub round_num: u32,
+    pub queue: VecDeque<MonsterKey>,
+    pub round_stalled: bool,
+    pub in_round: bool,
 }
 
+/// A battle's state and rng, taken between turns or rounds to rewind to later
+#[derive(Debug, Clone)]
+pub struct BattleSnapshot {
+    state: BattleState,
+    rng: StdRng,
+}
+
+
+impl Battle {
+    pub fn new(home: &Deck, oppo: &Deck) -> Self {
+        Self::from_rng(home, oppo, StdRng::from_entropy())
+    }
+
+    /// Same seed and decks always play out the same battle
+    pub fn with_seed(home: &Deck, oppo: &Deck, seed: u64) -> Self {
+        Self::from_rng(home, oppo, StdRng::seed_from_u64(seed))
+    }
+
+    pub fn from_rng(home: &Deck, oppo: &Deck, rng: StdRng) -> Self {
+        Self::from_rulesets(home, oppo, &[], rng)
+    }
 
-impl<'a> Battle<'a> {
-    pub fn new(reg: &'a Registry, home: &'a Vec<&'a str>, oppo: &'a Vec<&'a str>) -> Self {
-        let mut battledata = BattleData::new(reg, home, oppo);
+    /// A battle played under the match's rulesets
+    pub fn from_rulesets(home: &Deck, oppo: &Deck, rulesets: &[Ruleset], rng: StdRng) -> Self {
+        let mut battledata = BattleData::new(home, oppo, rulesets);
         battledata.register_all_team_buffs();
+        let targeting: Box<dyn TargetingPolicy> = if rulesets.contains(&Ruleset::MeleeMayhem) {
+            Box::new(MeleeMayhemTargeting::new(DefaultTargeting))
+        } else {
+            Box::new(DefaultTargeting)
+        };
         Self {
-            battledata
+            battledata,
+            rng,
+            round_num: 0,
+            config: BattleConfig::default(),
+            targeting,
+            queue: VecDeque::with_capacity(MAX_TURNS),
+            round_stalled: true,
+            in_round: false,
         }
     }
 
+    pub fn with_config(mut self, config: BattleConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Replaces the standard targeting rules, including the ones Melee Mayhem sets
+    pub fn with_targeting(mut self, targeting: impl TargetingPolicy + 'static) -> Self {
+        self.targeting = Box::new(targeting);
+        self
+    }
+
+    /// Plays until a team is wiped out, a round stalls or the round cap is hit, events stay buffered if recording
     pub fn game(&mut self) -> Outcome {
-        let mut i = 1;
-        let mut outcome = None;
-        let mut stalled = false;
-        while outcome.is_none() && !stalled {
-            // println!("\nRound {}:\n{:?}\n{:?}\n", i, 
-            //     self.battledata.home_alive.to_monster_string(&self.battledata),
-            //     self.battledata.oppo_alive.to_monster_string(&self.battledata)
-            // );
-            stalled = self.round();
-            outcome = self.battledata.determine_winner();
-            i += 1;
+        self.play(|_| ())
+    }
+
+    /// Plays the game and passes every event to sink at the end of each round
+    pub fn game_with_sink(&mut self, sink: &mut impl EventSink) -> Outcome {
+        self.battledata.record_events();
+        let outcome = self.play(|battle| battle.flush_events(sink));
+        self.flush_events(sink);
+        outcome
+    }
+
+    fn play(&mut self, mut after_round: impl FnMut(&mut Self)) -> Outcome {
+        let outcome = loop {
+            let stalled = self.round();
+            let outcome = self.game_over(stalled);
+            after_round(self);
+            if let Some(outcome) = outcome {
+                break outcome;
+            }
+        };
+        self.battledata.emit(BattleEvent::Outcome(outcome));
+        outcome
+    }
+
+    /// Checked after each round, None if the battle goes on
+    fn game_over(&mut self, stalled: bool) -> Option<Outcome> {
+        if let Some(outcome) = self.battledata.determine_winner() {
+            return Some(outcome);
+        }
+        if self.config.reached_max_rounds(self.round_num) {
+            self.battledata.emit(BattleEvent::RoundLimit);
+            return Some(Outcome::Draw);
         }
-        outcome.unwrap_or(Outcome::Draw)
+        if stalled && !self.config.fatigue_breaks_stalls() {
+            self.battledata.emit(BattleEvent::Stall);
+            return Some(Outcome::Draw);
+        }
+        None
     }
-    
+
+    /// Starts a round, plays a turn or ends the round, whichever is next, with chance for any randomness
+    /// Returns the outcome once the battle is over
+    pub fn step(&mut self, chance: &mut dyn Chance) -> Option<Outcome> {
+        if !self.in_round {
+            self.start_round_with(chance);
+            return None;
+        }
+        if self.next_turn_with(chance).is_some() {
+            return None;
+        }
+        let stalled = self.end_round();
+        let outcome = self.game_over(stalled)?;
+        self.battledata.emit(BattleEvent::Outcome(outcome));
+        Some(outcome)
+    }
+
+    /// Runs f with the battle's own rng as the chance
+    fn with_rng<T>(&mut self, f: impl FnOnce(&mut Self, &mut dyn Chance) -> T) -> T {
+        let mut rng = std::mem::replace(&mut self.rng, StdRng::from_seed(Default::default()));
+        let res = f(self, &mut rng);
+        self.rng = rng;
+        res
+    }
+
+    /// Plays a round, or the rest of the one in progress, returns true if it stalled
     pub fn round(&mut self) -> bool {
-        let mss = MonsterSpeed::get_vec(&self.battledata);
-        let mut stalled = true;
-        for ms in RoundRobinIter::new(mss) {
-            let tk = targeting::target_for(&self.battledata, &ms.mk);
-            if tk.is_none() {
-                continue;
-            }
-            let tk = tk.unwrap();
-            if attacking::attack(&mut self.battledata, &ms.mk, &tk) {
-                stalled = false;
+        if !self.in_round {
+            self.start_round();
+        }
+        while self.next_turn().is_some() {}
+        self.end_round()
+    }
+
+    /// Ticks round start effects and queues every monster's turn
+    pub fn start_round(&mut self) {
+        self.with_rng(|battle, rng| battle.start_round_with(rng));
+    }
+
+    pub fn start_round_with(&mut self, chance: &mut dyn Chance) {
+        self.round_num += 1;
+        self.battledata.emit(BattleEvent::RoundStart(self.round_num));
+        self.battledata.tick_effects(Tick::RoundStart);
+
+        self.battledata.speed_changed = false;
+        let order = RoundRobinIter::new(MonsterSpeed::alive(&self.battledata, self.config.tiebreaks), chance);
+        self.queue_turns(order);
+        self.round_stalled = true;
+        self.in_round = true;
+    }
+
+    /// Plays the next queued turn and returns whose it was, None once the round has no turns left
+    pub fn next_turn(&mut self) -> Option<MonsterKey> {
+        self.with_rng(|battle, rng| battle.next_turn_with(rng))
+    }
+
+    pub fn next_turn_with(&mut self, chance: &mut dyn Chance) -> Option<MonsterKey> {
+        let mk = self.queue.pop_front()?;
+        if self.turn_with(&mk, chance) {
+            self.round_stalled = false;
+        }
+
+        // monsters still to go are reordered by their new speeds
+        if self.battledata.speed_changed {
+            self.battledata.speed_changed = false;
+            let rest = self.queue.make_contiguous();
+            let order = RoundRobinIter::new(MonsterSpeed::alive_in(&self.battledata, rest, self.config.tiebreaks), chance);
+            self.queue_turns(order);
+        }
+        Some(mk)
+    }
+
+    /// End of round damage and effects, returns true if the round stalled
+    pub fn end_round(&mut self) -> bool {
+        if self.battledata.has_ruleset(Ruleset::Earthquake) && self.battledata.deal_earthquake() {
+            self.round_stalled = false;
+        }
+        self.battledata.tick_effects(Tick::RoundEnd);
+        let fatigue = self.config.fatigue_damage(self.round_num);
+        if fatigue > 0 {
+            self.battledata.deal_fatigue(fatigue);
+        }
+        self.in_round = false;
+        self.round_stalled
+    }
+
+    /// Copies the battle's state, buffered events are left out
+    pub fn state(&self) -> BattleState {
+        let mut battledata = self.battledata.clone();
+        battledata.events = None;
+        BattleState {
+            battledata,
+            round_num: self.round_num,
+            queue: self.queue.clone(),
+            round_stalled: self.round_stalled,
+            in_round: self.in_round,
+        }
+    }
+
+    /// Moves the battle to state, the rng, config, targeting and buffered events stay as they are
+    pub fn set_state(&mut self, state: &BattleState) {
+        let events = self.battledata.events.take();
+        self.battledata.clone_from(&state.battledata);
+        self.battledata.events = events;
+        self.round_num = state.round_num;
+        self.queue.clone_from(&state.queue);
+        self.round_stalled = state.round_stalled;
+        self.in_round = state.in_round;
+    }
+
+    pub fn snapshot(&self) -> BattleSnapshot {
+        BattleSnapshot {
+            state: self.state(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Rewinds to snapshot, the config, targeting and buffered events stay as they are
+    pub fn restore(&mut self, snapshot: &BattleSnapshot) {
+        self.set_state(&snapshot.state);
+        self.rng = snapshot.rng.clone();
+    }
+
+    /// Replaces the queued turns, reusing the queue's buffer
+    fn queue_turns(&mut self, order: RoundRobinIter) {
+        self.queue.clear();
+        self.queue.extend(order.map(|ms| ms.mk));
+        if self.battledata.is_recording() {
+            self.battledata.emit(BattleEvent::TurnOrder(self.queue.iter().copied().collect()));
+        }
+    }
+
+    /// Plays out mk's turn, returns true if any of its attacks landed
+    pub fn turn(&mut self, mk: &MonsterKey) -> bool {
+        self.with_rng(|battle, rng| battle.turn_with(mk, rng))
+    }
+
+    pub fn turn_with(&mut self, mk: &MonsterKey, chance: &mut dyn Chance) -> bool {
+        if self.battledata.take_stun(mk) {
+            return false;
+        }
+        healing::heal_turn(&mut self.battledata, mk);
+
+        let monster = self.battledata.get(mk).expect("mk not in battle");
+        let strikes = if monster.has_ability(Ability::DoubleStrike) { 2 } else { 1 };
+        let mut landed = false;
+        // one attack per attack type, each with its own targeting
+        for attack_type in monster.get_attack_types() {
+            for _ in 0..strikes {
+                if !self.battledata.get(mk).expect("mk not in battle").is_alive() {
+                    return landed;
+                }
+                let tk = self.targeting.target(&self.battledata, mk, attack_type, chance);
+                if tk.is_none() {
+                    break;
+                }
+                let tk = tk.unwrap();
+                self.battledata.emit(BattleEvent::Target { mk: *mk, tk });
+                if attacking::attack_with(&mut self.battledata, mk, &tk, attack_type, chance) {
+                    landed = true;
+                }
             }
         }
-        stalled
+        landed
+    }
+
+    /// Start buffering events for take_events
+    pub fn record_events(&mut self) {
+        self.battledata.record_events();
     }
 
-    pub fn get(&self, mk: &MonsterKey) -> Option<&Monster<'a>> {
+    pub fn take_events(&mut self) -> Vec<BattleEvent> {
+        self.battledata.take_events()
+    }
+
+    fn flush_events(&mut self, sink: &mut impl EventSink) {
+        for event in self.battledata.take_events() {
+            sink.emit(event);
+        }
+    }
+
+    pub fn get(&self, mk: &MonsterKey) -> Option<&Monster> {
         self.battledata.get(mk)
     }
 
@@ -68,10 +325,37 @@ impl<'a> Battle<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{gamedata::registry::Registry, battles::{monsterkey::MonsterKey, attacking, roundrobiniter::RoundRobinIter, monsterspeed::MonsterSpeed, battlechecker::BattleChecker}};
+    use crate::{gamedata::registry::Registry, battles::{monsterkey::MonsterKey, attacking, roundrobiniter::RoundRobinIter, monsterspeed::{MonsterSpeed, GAME_TIEBREAKS}, battlechecker::BattleChecker, battleevent::BattleEvent, battleconfig::BattleConfig}};
+    use crate::cardparse::enums::{Ability, AttackType, Outcome};
+    use crate::battles::healing;
+    use crate::gamedata::statuseffect::{StatusEffect, Duration, StackRule, Tick};
+    use crate::battles::{battledata::BattleData, ruleset::Ruleset, chance::Chance, targetingpolicy::{TargetingPolicy, DefaultTargeting}};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use std::sync::Arc;
 
     use super::Battle;
     use crate::battles::targeting;
+    use crate::cardparse::carddata::{CardData, CardRef, Damage};
+    use crate::gamedata::deck::{Deck, DeckRules};
+
+    /// Decks for a scenario, elements aren't checked
+    fn decks(reg: &Registry, home: &[&str], oppo: &[&str]) -> (Deck, Deck) {
+        let deck = |cards: &[&str]| Deck::with_rules(reg, cards, DeckRules::sandbox()).unwrap_or_else(|e| panic!("{}", e));
+        (deck(home), deck(oppo))
+    }
+
+    /// A card from reg changed by edit, for scenarios no real card sets up
+    fn card_with(reg: &Registry, name: &str, edit: impl FnOnce(&mut CardData)) -> CardRef {
+        let mut card = (**reg.get_card(name).unwrap()).clone();
+        edit(&mut card);
+        Arc::new(card)
+    }
+
+    fn deck_of(reg: &Registry, summoner: &str, monsters: Vec<CardRef>) -> Deck {
+        Deck::from_cards(reg.get_card(summoner).unwrap().clone(), monsters, DeckRules::sandbox()).unwrap()
+    }
 
     #[test]
     fn test_melee_target() {
@@ -79,14 +363,15 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Kobold Bruiser"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let mk = MonsterKey::Home(0);
         let player = battle.battledata.get(&mk).unwrap();
         assert_eq!(player.get_name(), "Living Lava");
 
-        let enemy = targeting::target_for_melee(&battle.battledata, &mk).unwrap();
+        let enemy = targeting::target_for_melee(&battle.battledata, &mk, &mut battle.rng).unwrap();
         let enemy_mons = battle.battledata.get(&enemy).unwrap();
         assert_eq!(enemy_mons.get_name(), "Serpent of Eld");
 
@@ -98,11 +383,12 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Kobold Bruiser"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let pk = &MonsterKey::Home(1);
-        let target = targeting::target_for_melee(&battle.battledata, pk);
+        let target = targeting::target_for_melee(&battle.battledata, pk, &mut battle.rng);
         assert_eq!(target, None);
     }
 
@@ -112,11 +398,12 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let pk = &MonsterKey::Home(1);
-        let target = targeting::target_for_melee(&battle.battledata, pk).unwrap();
+        let target = targeting::target_for_melee(&battle.battledata, pk, &mut battle.rng).unwrap();
         assert_eq!(target, MonsterKey::Oppo(0));
     }
 
@@ -126,13 +413,14 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
 
         let mk = &MonsterKey::Home(0);
-        let tk = targeting::target_for_melee(&battle.battledata, mk).unwrap();
+        let tk = targeting::target_for_melee(&battle.battledata, mk, &mut battle.rng).unwrap();
 
-        attacking::attack_melee_or_ranged(&mut battle.battledata, mk, &tk);
+        attacking::attack_melee_or_ranged(&mut battle.battledata, mk, &tk, AttackType::Melee, &mut battle.rng);
 
         let target = battle.battledata.get(&tk).unwrap();
         assert_eq!(target.get_armor(), 0);
@@ -145,11 +433,12 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let mk = &MonsterKey::Home(3);
-        let target = targeting::target_for_ranged(&battle.battledata, mk).unwrap();
+        let target = targeting::target_for_ranged(&battle.battledata, mk, &mut battle.rng).unwrap();
         assert!(target == MonsterKey::Oppo(0) || target == MonsterKey::Oppo(1));
     }
 
@@ -159,11 +448,12 @@ mod tests {
 
         let home = vec!["Pyre", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let mk = &MonsterKey::Home(0);
-        let target = targeting::target_for_ranged(&battle.battledata, mk);
+        let target = targeting::target_for_ranged(&battle.battledata, mk, &mut battle.rng);
         assert_eq!(target, None);
     }
 
@@ -173,11 +463,12 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let mk = &MonsterKey::Oppo(2);
-        let target = targeting::target_for_magic(&battle.battledata, mk).unwrap();
+        let target = targeting::target_for_magic(&battle.battledata, mk, &mut battle.rng).unwrap();
         assert!(target == MonsterKey::Home(0) || target == MonsterKey::Home(1) || target == MonsterKey::Home(2) || target == MonsterKey::Home(3));
     }
 
@@ -187,13 +478,14 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         let mk = &MonsterKey::Oppo(2);
         let tk = &MonsterKey::Home(0);
 
-        attacking::attack_magic(&mut battle.battledata, mk, tk);
+        attacking::attack_magic(&mut battle.battledata, mk, tk, &mut battle.rng);
         let target = battle.battledata.get(tk).unwrap();
         assert_eq!(target.get_health(), 5);
         assert_eq!(target.get_armor(), 2);
@@ -205,8 +497,9 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
         
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
         let dmg = 1000;
         
         for i in 0..=2 {
@@ -216,9 +509,9 @@ mod tests {
             battle.battledata.deal_true_damage(&ok, dmg);
         }
 
-        let rem: Vec<_> = RoundRobinIter::new(MonsterSpeed::get_vec(&battle.battledata)).collect();
+        let rem: Vec<_> = RoundRobinIter::new(MonsterSpeed::get_vec(&battle.battledata, GAME_TIEBREAKS), &mut battle.rng).collect();
         assert_eq!(rem.len(), 1);
-        let guy = battle.battledata.monsters.get(&rem[0].mk).unwrap();
+        let guy = battle.battledata.get(&rem[0].mk).unwrap();
         assert_eq!(guy.get_name(), "Goblin Fireballer");
     }
 
@@ -228,16 +521,17 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
 
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         battle.battledata.deal_true_damage(&MonsterKey::Home(0), 1000);
         battle.battledata.deal_true_damage(&MonsterKey::Home(2), 1000);
         battle.battledata.deal_true_damage(&MonsterKey::Oppo(1), 1000);
 
-        let ms_vec = MonsterSpeed::get_vec(&battle.battledata);
-        for ms in RoundRobinIter::new(ms_vec) {
-            let tk = targeting::target_for(&battle.battledata, &ms.mk);
+        let ms_vec = MonsterSpeed::get_vec(&battle.battledata, GAME_TIEBREAKS);
+        for ms in RoundRobinIter::new(ms_vec, &mut battle.rng.clone()) {
+            let tk = targeting::target_for(&battle.battledata, &ms.mk, &mut battle.rng);
             if let Some(tk) = tk {
                 assert_ne!(ms.mk, tk);  // no self targeting
                 let target = battle.battledata.get(&tk).unwrap();
@@ -252,8 +546,9 @@ mod tests {
 
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
 
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let mut battle = Battle::new(&home, &oppo);
 
         battle.battledata.deal_true_damage(&MonsterKey::Home(0), 1000);
         battle.battledata.deal_true_damage(&MonsterKey::Home(2), 1000);
@@ -276,7 +571,8 @@ mod tests {
         let reg = Registry::from("assets/cards.csv");
         let home = vec!["Pyre", "Living Lava", "Magma Troll", "Kobold Bruiser", "Goblin Fireballer"];
         let oppo = vec!["Alric Stormbringer", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
-        let battle = Battle::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let battle = Battle::new(&home, &oppo);
         
         let troll = battle.battledata.get(&MonsterKey::Home(1)).unwrap();
         let kobold = battle.battledata.get(&MonsterKey::Home(2)).unwrap();
@@ -284,7 +580,7 @@ mod tests {
         
         assert_eq!(troll.get_speed(), troll.get_default_speed() + 1);
         assert_eq!(kobold.get_speed(), kobold.get_default_speed() + 1);
-        assert_eq!(pixie.get_damage(), pixie.get_default_damage() + 1);
+        assert_eq!(pixie.get_damage(), pixie.get_default_damage_for(AttackType::Magic) + 1);
     }
 
     #[test]
@@ -292,7 +588,8 @@ mod tests {
         let reg = Registry::from("assets/cards.csv");
         let home = vec!["Wizard of Eastwood", "Goblin Sorcerer"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal", "Ice Pixie"];
-        let battle = Battle::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let battle = Battle::new(&home, &oppo);
 
         let goblin = battle.battledata.get(&MonsterKey::Home(0)).unwrap();
         let serpent = battle.battledata.get(&MonsterKey::Oppo(0)).unwrap();
@@ -308,7 +605,8 @@ mod tests {
         let reg = Registry::from("assets/cards.csv");
         let home = vec!["Wizard of Eastwood", "Goblin Sorcerer"];
         let oppo = vec!["Tyrus Paladium", "Elven Defender", "Crystal Jaguar", "Peacebringer"];
-        let battle = Battle::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let battle = Battle::new(&home, &oppo);
 
         let elf = battle.battledata.get(&MonsterKey::Oppo(0)).unwrap();
         let jag = battle.battledata.get(&MonsterKey::Oppo(1)).unwrap();
@@ -322,19 +620,20 @@ mod tests {
         let reg = Registry::from("assets/new_cards.csv");
         let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Feasting Seaweed", "Sniping Narwhal", "Ice Pixie"];
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
 
-        let tk = targeting::target_for(&battle.battledata, &MonsterKey::Home(2)).unwrap();
-        let tk2 = targeting::target_for(&battle.battledata, &MonsterKey::Home(3)).unwrap();
+        let tk = targeting::target_for(&battle.battledata, &MonsterKey::Home(2), &mut battle.rng).unwrap();
+        let tk2 = targeting::target_for(&battle.battledata, &MonsterKey::Home(3), &mut battle.rng).unwrap();
 
         assert_eq!(tk, MonsterKey::Oppo(3));
         assert_eq!(tk2, MonsterKey::Oppo(3));
 
-        attacking::attack(&mut battle.battledata, &MonsterKey::Home(2), &tk);
-        let tk = targeting::target_for(&battle.battledata, &MonsterKey::Home(3)).unwrap();
+        attacking::attack(&mut battle.battledata, &MonsterKey::Home(2), &tk, &mut battle.rng);
+        let tk = targeting::target_for(&battle.battledata, &MonsterKey::Home(3), &mut battle.rng).unwrap();
         assert_eq!(tk, MonsterKey::Oppo(1));
 
-        let tk = targeting::target_for(&battle.battledata, &MonsterKey::Home(4)).unwrap();
+        let tk = targeting::target_for(&battle.battledata, &MonsterKey::Home(4), &mut battle.rng).unwrap();
         assert_eq!(tk, MonsterKey::Oppo(2));
     }
 
@@ -343,51 +642,218 @@ mod tests {
         let reg = Registry::from("assets/new_cards.csv");
         let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Elven Mystic"];
         let oppo = vec!["Wizard of Eastwood", "Unicorn Mustang", "Failed Summoner"];
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
 
         // 7 health 0 armor attacked by 3 melee (shield should result in 2 dmg)
         let mk_unicorn = MonsterKey::Oppo(0);
-        let mk_lava = targeting::target_for(&battle.battledata, &mk_unicorn).unwrap();
-        attacking::attack(&mut battle.battledata, &mk_unicorn, &mk_lava);
+        let mk_lava = targeting::target_for(&battle.battledata, &mk_unicorn, &mut battle.rng).unwrap();
+        attacking::attack(&mut battle.battledata, &mk_unicorn, &mk_lava, &mut battle.rng);
 
         let lava = battle.battledata.get(&mk_lava).unwrap();
         assert_eq!(lava.get_health(), 5);
 
         // 10 health 0 armor attacked by 1 magic dmg (0 with void)
         let mk_mystic = MonsterKey::Home(4);
-        attacking::attack(&mut battle.battledata, &mk_mystic, &mk_unicorn);
+        attacking::attack(&mut battle.battledata, &mk_mystic, &mk_unicorn, &mut battle.rng);
 
         let unicorn = battle.battledata.get(&mk_unicorn).unwrap();
         assert_eq!(unicorn.get_health(), 10);
     }
 
+    #[test]
     fn test_flying_dodge_true_strike() {
-        // run 10x, if passes once then it's good
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Dhampir Stalker"];
+        let oppo = vec!["Bortus", "Serpent of Eld", "Feasting Seaweed", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let battle = Battle::new(&home, &oppo);
+
+        let spy = battle.get(&MonsterKey::Home(3)).unwrap();
+        let dhampir = battle.get(&MonsterKey::Home(4)).unwrap();
+        let eld = battle.get(&MonsterKey::Oppo(0)).unwrap();
+        let pixie = battle.get(&MonsterKey::Oppo(3)).unwrap();
+
+        // flying at the same speed, dodge and 1 speed faster
+        assert_eq!(attacking::evade_chance(spy, pixie, false), 0.25);
+        assert!((attacking::evade_chance(spy, eld, false) - 0.35).abs() < 1e-6);
+        assert_eq!(attacking::evade_chance(dhampir, eld, false), 0.0);
+
+        // same seed evades the same attacks
+        for seed in 0..10 {
+            let mut first = Battle::with_seed(&home, &oppo, seed);
+            let mut second = Battle::with_seed(&home, &oppo, seed);
+            for battle in [&mut first, &mut second] {
+                attacking::attack(&mut battle.battledata, &MonsterKey::Home(3), &MonsterKey::Oppo(3), &mut battle.rng);
+                attacking::attack(&mut battle.battledata, &MonsterKey::Home(3), &MonsterKey::Oppo(0), &mut battle.rng);
+            }
+            for mk in [MonsterKey::Oppo(0), MonsterKey::Oppo(3)] {
+                assert_eq!(first.get(&mk).unwrap().get_health(), second.get(&mk).unwrap().get_health());
+                assert_eq!(first.get(&mk).unwrap().get_armor(), second.get(&mk).unwrap().get_armor());
+            }
+        }
+    }
+
+    #[test]
+    fn test_seeded_game_repeats() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
+        let oppo = vec!["Bortus", "Serpent of Eld", "Feasting Seaweed", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+
+        for seed in 0..10 {
+            let mut first = Battle::with_seed(&home, &oppo, seed);
+            let mut second = Battle::with_seed(&home, &oppo, seed);
+            assert_eq!(first.game(), second.game());
+            for (mk, mons) in first.battledata.monsters() {
+                assert_eq!(mons.get_health(), second.get(&mk).unwrap().get_health());
+            }
+        }
+    }
+
+    #[test]
+    fn test_game_events() {
         let reg = Registry::from("assets/new_cards.csv");
         let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
         let oppo = vec!["Bortus", "Serpent of Eld", "Feasting Seaweed", "Sniping Narwhal", "Ice Pixie"];
-        let mut battle = Battle::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+
+        let mut events = Vec::new();
+        let outcome = battle.game_with_sink(&mut events);
+
+        assert_eq!(events[0], BattleEvent::RoundStart(1));
+        assert!(matches!(events[1], BattleEvent::TurnOrder(_)));
+        assert_eq!(events.last(), Some(&BattleEvent::Outcome(outcome)));
+
+        // every dead monster died exactly once
+        let deaths: Vec<_> = events.iter().filter_map(|event| match event {
+            BattleEvent::Death(mk) => Some(*mk),
+            _ => None,
+        }).collect();
+        for (mk, mons) in battle.battledata.monsters() {
+            let count = deaths.iter().filter(|x| **x == mk).count();
+            assert_eq!(count, if mons.is_alive() { 0 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn test_damage_events() {
+        let reg = Registry::from("assets/cards.csv");
+        let home = vec!["Pyre", "Living Lava", "Kobold Bruiser"];
+        let oppo = vec!["Bortus", "Serpent of Eld", "Sniping Narwhal"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+
+        let mk_serpent = MonsterKey::Oppo(0);
+        battle.battledata.deal_damage(&mk_serpent, 3);
+        battle.battledata.deal_true_damage(&mk_serpent, 1000);
+
+        let exp = vec![
+            BattleEvent::ArmorDamage { tk: mk_serpent, dmg: 2 },
+            BattleEvent::HealthDamage { tk: mk_serpent, dmg: 5 },
+            BattleEvent::Death(mk_serpent),
+        ];
+        assert_eq!(battle.take_events(), exp);
+    }
 
-        // 25% chance of evade
-        let mk_spy = MonsterKey::Home(3);
-        let mk_pixie = MonsterKey::Oppo(3);
-        attacking::attack(&mut battle.battledata, &mk_spy, &mk_pixie);
-        let pixie = battle.battledata.get(&mk_pixie).unwrap();
-        assert_eq!(pixie.get_health(), 1);
+    #[test]
+    fn test_fatigue_and_round_limit() {
+        let reg = Registry::from("assets/cards.csv");
+        let home = vec!["Pyre", "Peaceful Giant"];
+        let oppo = vec!["Alric Stormbringer", "Failed Summoner"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+
+        // nobody can attack so the first round stalls
+        let mut battle = Battle::with_seed(&home, &oppo, 0).with_config(BattleConfig::unlimited());
+        battle.record_events();
+        assert_eq!(battle.game(), Outcome::Draw);
+        assert_eq!(battle.round_num, 1);
+        assert!(battle.take_events().contains(&BattleEvent::Stall));
+
+        // 8 health giant outlasts the 4 health summoner once fatigue kicks in at round 20
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+        assert_eq!(battle.game(), Outcome::Win);
+        assert_eq!(battle.round_num, 22);
+        assert_eq!(battle.get(&MonsterKey::Home(0)).unwrap().get_health(), 2);
+        let events = battle.take_events();
+        assert!(events.contains(&BattleEvent::Fatigue(3)));
+        assert!(!events.contains(&BattleEvent::Stall));
+
+        let config = BattleConfig { max_rounds: Some(5), ..BattleConfig::unlimited() };
+        let mut battle = Battle::with_seed(&home, &oppo, 0).with_config(BattleConfig { fatigue_start: Some(30), fatigue_growth: 1, ..config });
+        battle.record_events();
+        assert_eq!(battle.game(), Outcome::Draw);
+        assert_eq!(battle.round_num, 5);
+        assert!(battle.take_events().contains(&BattleEvent::RoundLimit));
+    }
+
+    #[test]
+    fn test_heal_and_tank_heal() {
+        let reg = Registry::from("assets/cards.csv");
+        let home = vec!["Lyanna Natura", "Peaceful Giant", "Cerberus", "Divine Healer"];
+        let oppo = vec!["Pyre", "Kobold Bruiser"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+
+        let mk_giant = MonsterKey::Home(0);
+        let mk_cerberus = MonsterKey::Home(1);
+        let mk_healer = MonsterKey::Home(2);
+
+        // 6 health cerberus heals 2 of its own health
+        battle.battledata.deal_true_damage(&mk_cerberus, 3);
+        healing::heal_turn(&mut battle.battledata, &mk_cerberus);
+        assert_eq!(battle.get(&mk_cerberus).unwrap().get_health(), 5);
+        healing::heal_turn(&mut battle.battledata, &mk_cerberus);
+        assert_eq!(battle.get(&mk_cerberus).unwrap().get_health(), 6);
+
+        // 9 health giant in first position gets 3 from the tank healer
+        battle.battledata.deal_true_damage(&mk_giant, 5);
+        healing::heal_turn(&mut battle.battledata, &mk_healer);
+        assert_eq!(battle.get(&mk_giant).unwrap().get_health(), 7);
+    }
 
-        // 25% chance of evade
-        let mk_eld = MonsterKey::Oppo(0);
-        attacking::attack(&mut battle.battledata, &mk_spy, &mk_eld);
-        let eld = battle.battledata.get(&mk_eld).unwrap();
-        assert_eq!(eld.get_armor(), 2);
-        assert_eq!(eld.get_health(), 5);
+    #[test]
+    fn test_repair_and_cleanse() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Tarsa", "Living Lava", "Scavo Hireling", "Scavo Chemist"];
+        let oppo = vec!["Bortus", "Serpent of Eld"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+
+        let mk_lava = MonsterKey::Home(0);
+        let mk_hireling = MonsterKey::Home(1);
+        let mk_chemist = MonsterKey::Home(2);
+
+        // armor never goes over its max
+        battle.battledata.deal_damage(&mk_lava, 1);
+        healing::heal_turn(&mut battle.battledata, &mk_hireling);
+        assert_eq!(battle.get(&mk_lava).unwrap().get_armor(), 2);
+        healing::heal_turn(&mut battle.battledata, &mk_hireling);
+        assert_eq!(battle.get(&mk_lava).unwrap().get_armor(), 2);
+
+        battle.battledata.add_effect(&mk_lava, StatusEffect::stun(mk_chemist));
+        healing::heal_turn(&mut battle.battledata, &mk_chemist);
+        assert!(battle.get(&mk_lava).unwrap().get_effects().is_empty());
+
+        let exp = vec![
+            BattleEvent::ArmorDamage { tk: mk_lava, dmg: 1 },
+            BattleEvent::Repair { mk: mk_hireling, tk: mk_lava, amount: 1 },
+            BattleEvent::Cleanse { mk: mk_chemist, tk: mk_lava },
+        ];
+        assert_eq!(battle.take_events(), exp);
     }
+
     #[test]
     fn test_taunt_close_range() {
         let home = vec!["Tarsa", "Living Lava", "Venari Spellsmith", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
         let oppo = vec!["Obsidian", "Venari Knifer", "Mycelic Slipspawn", "Goblin Tower"];
         let reg = Registry::from("assets/new_cards.csv");
-        let bc = BattleChecker::new(&reg, &home, &oppo);
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::new(&home, &oppo);
 
 
         bc.assert_target("Living Lava", "Venari Knifer");
@@ -397,14 +863,523 @@ mod tests {
         bc.assert_target("Lava Spider", "Mycelic Slipspawn");
     }
     
+    #[test]
+    fn test_blast() {
+        let home = vec!["Pyre", "Kobold Bruiser", "Fire Elemental"];
+        let oppo = vec!["Bortus", "Merdaali Guardian", "Sniping Narwhal", "Feasting Seaweed"];
+        let reg = Registry::from("assets/new_cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        assert!(bc.attack("Fire Elemental", "Sniping Narwhal"));
+        bc.assert_health("Sniping Narwhal", 2);
+        bc.assert_health("Merdaali Guardian", 2);
+        bc.assert_health("Feasting Seaweed", 1);
+    }
+
+    #[test]
+    fn test_blast_shield_void() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let guardian = card_with(&reg, "Merdaali Guardian", |card| { card.abilities.insert(Ability::Shield); });
+        let seaweed = card_with(&reg, "Feasting Seaweed", |card| { card.abilities.insert(Ability::Void); });
+        let oppo = deck_of(&reg, "Wizard of Eastwood", vec![guardian, reg.get_card("Sniping Narwhal").unwrap().clone(), seaweed]);
+
+        // 4 ranged blasts 2, halved to 1 by shield but not by void
+        let ranged = card_with(&reg, "Fire Elemental", |card| card.damage.ranged = 4);
+        let home = deck_of(&reg, "Pyre", vec![ranged]);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+        assert!(bc.attack("Fire Elemental", "Sniping Narwhal"));
+        bc.assert_health("Merdaali Guardian", 2);
+        bc.assert_health("Feasting Seaweed", 0);
+
+        // 4 magic blasts 2, halved to 1 by void but not by shield
+        let magic = card_with(&reg, "Fire Elemental", |card| card.damage = Damage { magic: 4, ..Damage::default() });
+        let home = deck_of(&reg, "Pyre", vec![magic]);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+        assert!(bc.attack("Fire Elemental", "Sniping Narwhal"));
+        bc.assert_health("Merdaali Guardian", 1);
+        bc.assert_health("Feasting Seaweed", 1);
+    }
+
+    #[test]
+    fn test_trample() {
+        let home = vec!["Bortus", "Diemonshark"];
+        let oppo = vec!["Tarsa", "Serpentine Spy", "Kobold Bruiser"];
+        let reg = Registry::from("assets/new_cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        assert!(bc.turn("Diemonshark"));
+        bc.assert_health("Serpentine Spy", 0);
+        bc.assert_health("Kobold Bruiser", 2);
+    }
+
+    #[test]
+    fn test_double_strike() {
+        let home = vec!["General Sloan", "Merdaali Guardian", "Pelacor Arbalest"];
+        let oppo = vec!["Tarsa", "Living Lava"];
+        let reg = Registry::from("assets/new_cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        // 3 ranged halved by shield, once into armor and once into health
+        assert!(bc.turn("Pelacor Arbalest"));
+        bc.assert_armor("Living Lava", 0);
+        bc.assert_health("Living Lava", 5);
+    }
+
+    #[test]
+    fn test_stun() {
+        let home = vec!["Malric Inferno", "Pit Ogre"];
+        let oppo = vec!["Alric Stormbringer", "Haunted Spirit"];
+        let reg = Registry::from("assets/cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+
+        let mut stunned = 0;
+        for seed in 0..20 {
+            let mut bc = BattleChecker::with_seed(&home, &oppo, seed);
+            // spirit is 1 faster and can evade
+            if !bc.attack("Pit Ogre", "Haunted Spirit") {
+                continue;
+            }
+            if !bc.monster("Haunted Spirit").has_effect(Ability::Stun) {
+                continue;
+            }
+            stunned += 1;
+
+            // stunned spirit loses its turn, then the stun wears off
+            assert!(!bc.turn("Haunted Spirit"));
+            bc.assert_health("Pit Ogre", 7);
+            bc.assert_stunned("Haunted Spirit", false);
+        }
+        assert!(stunned > 0 && stunned < 20);
+    }
+
     #[test]
     fn test_thorns_life_leech() {
+        let home = vec!["Pyre", "Kobold Bruiser"];
+        let oppo = vec!["Contessa L'ament", "Cursed Windeku", "Life Sapper"];
+        let reg = Registry::from("assets/new_cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        // kobold takes 2 thorns damage back for hitting the windeku
+        assert!(bc.attack("Kobold Bruiser", "Cursed Windeku"));
+        bc.assert_health("Cursed Windeku", 7);
+        bc.assert_health("Kobold Bruiser", 1);
+
+        // sapper takes the kobold's last health and keeps half of it rounded up
+        assert!(bc.attack("Life Sapper", "Kobold Bruiser"));
+        bc.assert_health("Kobold Bruiser", 0);
+        bc.assert_health("Life Sapper", 3);
+        assert_eq!(bc.monster("Life Sapper").get_max_health(), 3);
+    }
 
+    #[test]
+    fn test_retaliate_magic_reflect() {
+        let home = vec!["Pyre", "Kobold Bruiser"];
+        let oppo = vec!["Lyanna Natura", "Stonesplitter Orc"];
+        let reg = Registry::from("assets/cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+
+        let mut retaliated = 0;
+        for seed in 0..20 {
+            let mut bc = BattleChecker::with_seed(&home, &oppo, seed);
+            assert!(bc.attack("Kobold Bruiser", "Stonesplitter Orc"));
+            bc.assert_armor("Stonesplitter Orc", 0);
+            if bc.monster("Kobold Bruiser").get_health() == 0 {
+                retaliated += 1;
+            } else {
+                bc.assert_health("Kobold Bruiser", 3);
+            }
+        }
+        assert!(retaliated > 0 && retaliated < 20);
+
+        // 1 magic reflects back as 1 true damage
+        let home = vec!["Pyre", "Ice Pixie"];
+        let oppo = vec!["Wizard of Eastwood", "Failed Summoner"];
+        let reg = Registry::from("assets/new_cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+        assert!(bc.attack("Ice Pixie", "Failed Summoner"));
+        bc.assert_health("Failed Summoner", 3);
+        bc.assert_health("Ice Pixie", 0);
+    }
+
+    #[test]
+    fn test_retaliate_with_melee() {
+        let reg = Registry::from("assets/leveled_cards.csv");
+        let home = deck_of(&reg, "Pyre", vec![reg.get_card("Kobold Bruiser").unwrap().clone()]);
+
+        // the mage's first attack is its 1 magic but it strikes back with its 2 melee
+        let mage = card_with(&reg, "Battle Mage", |card| { card.abilities.insert(Ability::Retaliate); });
+        let oppo = deck_of(&reg, "Contessa L'ament", vec![mage.clone()]);
+        let mut retaliated = 0;
+        for seed in 0..20 {
+            let mut bc = BattleChecker::with_seed(&home, &oppo, seed);
+            assert!(bc.attack("Kobold Bruiser", "Battle Mage"));
+            match bc.monster("Kobold Bruiser").get_health() {
+                1 => retaliated += 1,
+                health => assert_eq!(health, 3),
+            }
+        }
+        assert!(retaliated > 0);
+
+        // with no melee attack there's nothing to strike back with
+        let mage = card_with(&reg, "Battle Mage", |card| {
+            card.abilities.insert(Ability::Retaliate);
+            card.damage.melee = 0;
+        });
+        let oppo = deck_of(&reg, "Contessa L'ament", vec![mage]);
+        for seed in 0..20 {
+            let mut bc = BattleChecker::with_seed(&home, &oppo, seed);
+            assert!(bc.attack("Kobold Bruiser", "Battle Mage"));
+            bc.assert_health("Kobold Bruiser", 3);
+        }
+    }
+
+    #[test]
+    fn test_magic_reflect_damage_dealt() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let pixie = card_with(&reg, "Ice Pixie", |card| {
+            card.damage.magic = 4;
+            card.health = 5;
+        });
+        let home = deck_of(&reg, "Pyre", vec![pixie]);
+        let summoner = card_with(&reg, "Failed Summoner", |card| { card.abilities.insert(Ability::Void); });
+        let oppo = deck_of(&reg, "Wizard of Eastwood", vec![summoner]);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        // void halves the 4 magic to 2 and half of those 2 comes back
+        assert!(bc.attack("Ice Pixie", "Failed Summoner"));
+        bc.assert_health("Failed Summoner", 2);
+        bc.assert_health("Ice Pixie", 4);
     }
 
     #[test]
     fn test_monster_buff_allies_and_removal_on_death() {
+        let home = vec!["Tyrus Paladium", "Silvershield Warrior", "Silvershield Knight", "Truthspeaker"];
+        let oppo = vec!["Alric Stormbringer", "Undead Priest", "Kobold Bruiser"];
+        let reg = Registry::from("assets/cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        // inspire, protect and the summoner's armor on allies, weaken on enemies
+        assert_eq!(bc.monster("Silvershield Warrior").get_damage(), 2);
+        bc.assert_armor("Silvershield Warrior", 4);
+        bc.assert_health("Silvershield Warrior", 2);
+        bc.assert_armor("Silvershield Knight", 4);
+        bc.assert_health("Truthspeaker", 1);
+        assert_eq!(bc.monster("Kobold Bruiser").get_damage(), 2);
+
+        bc.deal_true_damage("Truthspeaker", 1000);
+        bc.assert_armor("Silvershield Warrior", 2);
+        bc.assert_armor("Silvershield Knight", 2);
+
+        bc.deal_true_damage("Silvershield Knight", 1000);
+        assert_eq!(bc.monster("Silvershield Warrior").get_damage(), 1);
+
+        bc.deal_true_damage("Undead Priest", 1000);
+        bc.assert_health("Silvershield Warrior", 3);
+        assert_eq!(bc.monster("Silvershield Warrior").get_max_health(), 3);
+    }
+
+    #[test]
+    fn test_same_aura_from_two_monsters() {
+        let home = vec!["Pyre", "Pit Ogre"];
+        let oppo = vec!["Contessa L'ament", "Goblin Shaman", "Undead Priest"];
+        let reg = Registry::from("assets/cards.csv");
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut bc = BattleChecker::with_seed(&home, &oppo, 0);
+
+        // both weaken but it only counts once, and stays until the 2nd one dies
+        bc.assert_health("Pit Ogre", 6);
+        bc.deal_true_damage("Goblin Shaman", 1000);
+        bc.assert_health("Pit Ogre", 6);
+        assert_eq!(bc.monster("Pit Ogre").get_max_health(), 6);
+
+        bc.deal_true_damage("Undead Priest", 1000);
+        bc.assert_health("Pit Ogre", 7);
+        assert_eq!(bc.monster("Pit Ogre").get_max_health(), 7);
+    }
+
+    #[test]
+    fn test_scavenger_on_death() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Contessa L'ament", "Riftwing"];
+        let oppo = vec!["Pyre", "Kobold Bruiser", "Goblin Fireballer"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+
+        let mk_rift = MonsterKey::Home(0);
+        let mk_kobold = MonsterKey::Oppo(0);
+        let mk_fire = MonsterKey::Oppo(1);
+        battle.battledata.deal_true_damage(&mk_kobold, 1000);
+        battle.battledata.deal_true_damage(&mk_fire, 1000);
+        assert_eq!(battle.get(&mk_rift).unwrap().get_max_health(), 7);
+        assert_eq!(battle.get(&mk_rift).unwrap().get_health(), 7);
+
+        // survivors react after the death is announced
+        let events: Vec<BattleEvent> = battle.take_events().into_iter()
+            .filter(|e| matches!(e, BattleEvent::Death(_) | BattleEvent::Scavenge { .. }))
+            .collect();
+        assert_eq!(events, vec![
+            BattleEvent::Death(mk_kobold),
+            BattleEvent::Scavenge { mk: mk_rift, amount: 1 },
+            BattleEvent::Death(mk_fire),
+            BattleEvent::Scavenge { mk: mk_rift, amount: 1 },
+        ]);
+
+        // the scavenger itself dying gives nothing
+        battle.battledata.deal_true_damage(&mk_rift, 1000);
+        assert!(battle.take_events().iter().all(|e| !matches!(e, BattleEvent::Scavenge { .. })));
+    }
+
+    #[test]
+    fn test_status_effects() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Pyre", "Kobold Bruiser"];
+        let oppo = vec!["Contessa L'ament", "Riftwing"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+        let mk = MonsterKey::Home(0);
+        let bd = &mut battle.battledata;
+        assert_eq!(bd.get(&mk).unwrap().get_speed(), 3);
+
+        // a refreshed slow stays a single entry and the getter reads through it
+        let slow = StatusEffect::new(Ability::Speed(-1), None, Duration::Rounds(1), StackRule::Refresh, Tick::RoundEnd);
+        assert!(bd.add_effect(&mk, slow.clone()));
+        assert!(!bd.add_effect(&mk, slow));
+        assert_eq!(bd.get(&mk).unwrap().get_effects().len(), 1);
+        assert_eq!(bd.get(&mk).unwrap().get_speed(), 2);
+        assert!(bd.speed_changed);
+
+        bd.tick_effects(Tick::RoundStart);
+        assert_eq!(bd.get(&mk).unwrap().get_speed(), 2);
+        bd.tick_effects(Tick::RoundEnd);
+        assert_eq!(bd.get(&mk).unwrap().get_speed(), 3);
+        assert!(bd.take_events().contains(&BattleEvent::EffectExpired { mk, kind: Ability::Speed(-1) }));
+
+        // dispel takes off buffs, cleanse takes off debuffs and gives the health back
+        let rage = StatusEffect::new(Ability::Melee(1), None, Duration::Rounds(2), StackRule::Stack, Tick::RoundStart);
+        let weaken = StatusEffect::new(Ability::Health(-1), None, Duration::UntilRemoved, StackRule::Stack, Tick::RoundEnd);
+        bd.add_effect(&mk, rage);
+        bd.add_effect(&mk, weaken);
+        assert_eq!(bd.get(&mk).unwrap().get_damage(), 3);
+        assert_eq!(bd.get(&mk).unwrap().get_health(), 2);
+
+        let monster = bd.get_mut(&mk).unwrap();
+        assert_eq!(monster.dispel().len(), 1);
+        assert_eq!(monster.get_damage(), 2);
+        assert_eq!(monster.cleanse().len(), 1);
+        assert_eq!(monster.get_health(), 3);
+        assert_eq!(monster.get_max_health(), 3);
+    }
+
+    /// House rule where monsters that can't reach anyone hit the front enemy instead
+    #[derive(Debug)]
+    struct FrontLine(DefaultTargeting);
+
+    impl TargetingPolicy for FrontLine {
+        fn target(&self, bd: &BattleData, mk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> Option<MonsterKey> {
+            let damage = bd.get(mk).unwrap().get_damage_for(attack_type);
+            self.0.target(bd, mk, attack_type, rng).or_else(|| if damage > 0 { targeting::target_first_pos(bd, mk) } else { None })
+        }
+    }
+
+    #[test]
+    fn test_targeting_policy() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Pyre", "Goblin Fireballer", "Kobold Bruiser"];
+        let oppo = vec!["Contessa L'ament", "Riftwing"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mk_kobold = MonsterKey::Home(1);
+
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+        battle.turn(&mk_kobold);
+        assert!(battle.take_events().is_empty());
+
+        let mut battle = Battle::with_seed(&home, &oppo, 0).with_targeting(FrontLine(DefaultTargeting));
+        battle.record_events();
+        battle.turn(&mk_kobold);
+        assert_eq!(battle.take_events()[0], BattleEvent::Target { mk: mk_kobold, tk: MonsterKey::Oppo(0) });
+    }
+
+    #[test]
+    fn test_rulesets() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Pyre", "Goblin Fireballer", "Kobold Bruiser"];
+        let oppo = vec!["Contessa L'ament", "Riftwing"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mk_fire = MonsterKey::Home(0);
+        let mk_kobold = MonsterKey::Home(1);
+        let mk_rift = MonsterKey::Oppo(0);
+        let with = |rulesets: &[Ruleset]| Battle::from_rulesets(&home, &oppo, rulesets, StdRng::seed_from_u64(0));
+
+        // earthquake skips flyers, the riftwing scavenges the fireballer
+        let mut battle = with(&[Ruleset::Earthquake]);
+        assert!(battle.battledata.deal_earthquake());
+        assert_eq!(battle.get(&mk_kobold).unwrap().get_health(), 1);
+        assert!(!battle.get(&mk_fire).unwrap().is_alive());
+        assert_eq!(battle.get(&mk_rift).unwrap().get_health(), 6);
+
+        // the slowest goes first and being slower helps evade
+        let mut battle = with(&[Ruleset::ReverseSpeed]);
+        battle.record_events();
+        battle.round();
+        assert!(matches!(&battle.take_events()[1], BattleEvent::TurnOrder(order) if order[0] == mk_fire));
+        let fire = battle.get(&mk_fire).unwrap();
+        let rift = battle.get(&mk_rift).unwrap();
+        assert!((attacking::evade_chance(rift, fire, true) - 0.1).abs() < 1e-6);
+
+        let battle = with(&[Ruleset::ArmoredUp, Ruleset::BackToBasics]);
+        assert_eq!(battle.get(&mk_kobold).unwrap().get_armor(), 2);
+        assert_eq!(battle.get(&mk_kobold).unwrap().get_max_armor(), 2);
+        assert!(!battle.get(&mk_rift).unwrap().has_ability(Ability::Flying));
+
+        let battle = with(&[Ruleset::SuperSneak]);
+        assert!(battle.get(&mk_kobold).unwrap().has_ability(Ability::Sneak));
+        assert!(!battle.get(&mk_fire).unwrap().has_ability(Ability::Sneak));
+
+        // a melee monster out of position only attacks under melee mayhem
+        let mut battle = with(&[]);
+        assert_eq!(battle.targeting.target(&battle.battledata, &mk_kobold, AttackType::Melee, &mut battle.rng), None);
+        let mut battle = with(&[Ruleset::MeleeMayhem]);
+        assert_eq!(battle.targeting.target(&battle.battledata, &mk_kobold, AttackType::Melee, &mut battle.rng), Some(mk_rift));
+    }
+
+    #[test]
+    fn test_card_levels() {
+        let reg = Registry::from("assets/leveled_cards.csv");
+        let home = vec!["Pyre", "Goblin Fireballer", "Kobold Bruiser:5"];
+        let oppo = vec!["Contessa L'ament:1", "Riftwing:2"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let battle = Battle::with_seed(&home, &oppo, 0);
+
+        let kobold = battle.get(&MonsterKey::Home(1)).unwrap();
+        assert_eq!(kobold.get_level(), 5);
+        assert_eq!(kobold.get_health(), 4);
+        assert_eq!(kobold.get_damage(), 3);
+        assert!(kobold.has_ability(Ability::Reach));
+        assert_eq!(battle.get(&MonsterKey::Oppo(0)).unwrap().get_max_health(), 6);
+    }
+
+    #[test]
+    fn test_multiple_attack_types() {
+        let reg = Registry::from("assets/leveled_cards.csv");
+        let home = vec!["Tarsa", "Battle Mage"];
+        let oppo = vec!["Contessa L'ament", "Kobold Bruiser"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+        let mk = MonsterKey::Home(0);
+        let tk = MonsterKey::Oppo(0);
+
+        // melee+ only buffs the melee attack
+        let mage = battle.get(&mk).unwrap();
+        assert_eq!(mage.get_attack_types().collect::<Vec<_>>(), vec![AttackType::Magic, AttackType::Melee]);
+        assert_eq!(mage.get_damage_for(AttackType::Melee), 3);
+        assert_eq!(mage.get_damage_for(AttackType::Magic), 1);
+        assert_eq!(mage.get_damage_for(AttackType::Ranged), 0);
+
+        // magic goes first, then melee finishes the kobold off
+        assert!(battle.turn(&mk));
+        let exp = vec![
+            BattleEvent::Target { mk, tk },
+            BattleEvent::HealthDamage { tk, dmg: 1 },
+            BattleEvent::Target { mk, tk },
+            BattleEvent::HealthDamage { tk, dmg: 2 },
+            BattleEvent::Death(tk),
+        ];
+        assert_eq!(battle.take_events(), exp);
+    }
+
+    #[test]
+    fn test_slow_removed_mid_round() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Mother Khala", "Time Mage"];
+        let oppo = vec!["Pyre", "Kobold Bruiser", "Goblin Fireballer"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 0);
+        battle.record_events();
+
+        let mk_mage = MonsterKey::Home(0);
+        let mk_kobold = MonsterKey::Oppo(0);
+        assert_eq!(battle.get(&mk_kobold).unwrap().get_speed(), 2);
+
+        // mage goes first at speed 4, the kobold kills it and the fireballer is reordered
+        battle.battledata.deal_true_damage(&mk_mage, 3);
+        battle.round();
+        assert!(!battle.get(&mk_mage).unwrap().is_alive());
+        assert_eq!(battle.get(&mk_kobold).unwrap().get_speed(), 3);
+
+        let orders: Vec<_> = battle.take_events().into_iter().filter_map(|event| match event {
+            BattleEvent::TurnOrder(order) => Some(order),
+            _ => None,
+        }).collect();
+        assert_eq!(orders, vec![
+            vec![mk_mage, mk_kobold, MonsterKey::Oppo(1)],
+            vec![MonsterKey::Oppo(1)],
+        ]);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
+        let oppo = vec!["Bortus", "Serpent of Eld", "Feasting Seaweed", "Sniping Narwhal", "Ice Pixie"];
+        let (home, oppo) = decks(&reg, &home, &oppo);
+        let mut battle = Battle::with_seed(&home, &oppo, 3);
+        battle.round();
+        battle.start_round();
+        let first = battle.next_turn().unwrap();
+        battle.next_turn();
+        let rest: Vec<MonsterKey> = battle.queue.iter().copied().collect();
+
+        // rewinding mid round replays the same turns with the same rolls
+        let snapshot = battle.snapshot();
+        let health = |battle: &Battle| -> Vec<i32> {
+            battle.battledata.monsters().map(|(_, mons)| mons.get_health()).collect()
+        };
+        battle.record_events();
+        let outcome = battle.game();
+        let events = battle.take_events();
+        let end_health = health(&battle);
+
+        battle.restore(&snapshot);
+        assert_eq!(battle.queue.iter().copied().collect::<Vec<_>>(), rest);
+        assert!(!rest.contains(&first));
+        // the round in progress is finished before the next one starts
+        assert!(matches!(events[0], BattleEvent::Target { mk, .. } if mk == rest[0]) || !battle.get(&rest[0]).unwrap().is_alive());
+        assert_eq!(battle.game(), outcome);
+        assert_eq!(battle.take_events(), events);
+        assert_eq!(health(&battle), end_health);
+
+        // a branch where the front enemy dies first plays out differently
+        battle.restore(&snapshot);
+        battle.battledata.deal_true_damage(&MonsterKey::Oppo(0), 99);
+        battle.game();
+        assert_ne!(health(&battle), end_health);
+    }
+
+    #[test]
+    fn test_owned_battle() {
+        // the deck list and the registry are dropped before the battle is played
+        fn build(seed: u64) -> Battle {
+            let reg = Registry::from("assets/new_cards.csv");
+            let home = Deck::new(&reg, &["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker"]).unwrap();
+            let oppo = Deck::new(&reg, &["Kelya Frendul", "Serpent of Eld", "Feasting Seaweed", "Ice Pixie"]).unwrap();
+            Battle::with_seed(&home, &oppo, seed)
+        }
 
+        let mut battles: Vec<Battle> = (0..4).map(build).collect();
+        let outcomes: Vec<Outcome> = battles.iter_mut().map(|battle| battle.game()).collect();
+        let handles: Vec<_> = (0..4).map(|seed| std::thread::spawn(move || build(seed).game())).collect();
+        let threaded: Vec<Outcome> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+        assert_eq!(outcomes, threaded);
     }
 
     // taunt, blast, cleanse
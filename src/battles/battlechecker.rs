@@ -1,20 +1,32 @@
 use std::collections::HashSet;
 
-use crate::gamedata::{registry::Registry, monster::Monster};
+use crate::gamedata::{deck::Deck, monster::Monster};
 
-use super::{battle::Battle, monsterkey::MonsterKey, targeting};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::cardparse::enums::Ability;
+
+use super::{attacking, battle::Battle, monsterkey::MonsterKey};
 
 /// Wraps a battle and makes testing easier
-pub struct BattleChecker<'a> {
-    battle: Battle<'a>,
+pub struct BattleChecker {
+    battle: Battle,
 }
 
-impl<'a> BattleChecker<'a> {
+impl BattleChecker {
     /// Will not work if same monster on separate teams
-    pub fn new(reg: &'a Registry, home: &'a Vec<&'a str>, oppo: &'a Vec<&'a str>) -> Self {
-        let battle = Battle::new(reg, home, oppo);
+    pub fn new(home: &Deck, oppo: &Deck) -> Self {
+        Self::from_rng(home, oppo, StdRng::from_entropy())
+    }
+
+    pub fn with_seed(home: &Deck, oppo: &Deck, seed: u64) -> Self {
+        Self::from_rng(home, oppo, StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(home: &Deck, oppo: &Deck, rng: StdRng) -> Self {
+        let battle = Battle::from_rng(home, oppo, rng);
         if Self::has_same_monster_on_both_teams(home, oppo) {
-            panic!("does not support same monster on both teams\nhome: {:?}, oppo: {:?}", home, oppo);
+            panic!("does not support same monster on both teams\nhome: {}, oppo: {}", home, oppo);
         }
         
         Self {
@@ -22,36 +34,82 @@ impl<'a> BattleChecker<'a> {
         }
     }
 
-    fn has_same_monster_on_both_teams(home: &'a Vec<&'a str>, oppo: &'a Vec<&'a str>) -> bool {
+    fn has_same_monster_on_both_teams(home: &Deck, oppo: &Deck) -> bool {
         // put all oppo into a hash set
         let mut enemies = HashSet::new();
-        for name in oppo {
-            enemies.insert(name);
+        for card in &oppo.monsters {
+            enemies.insert(&card.name);
         }
-        for name in home {
-            if enemies.contains(name) {
+        for card in &home.monsters {
+            if enemies.contains(&card.name) {
                 return true;
             }
         }
         false
     }
 
-    pub fn assert_target(&self, subject: &str, target: &str) {
+    pub fn assert_target(&mut self, subject: &str, target: &str) {
         // pull out the monster and its mk
         // get the targeting of the mk as tk and get its name
         // assert that the name of the targeting of mk is the same as target
-        let (mk_sub, mons_sub) = self.get_mk_and_monster_by_name(subject).expect(&format!("{} could not be found", subject));
+        let mk_sub = self.get_mk_and_monster_by_name(subject).unwrap_or_else(|| panic!("{} could not be found", subject)).0;
         // let (mk_tar, mons_tar) = self.get_mk_and_monster_by_name(target).expect(&format!("{} could not be found", target));
-        let mk_tar = targeting::target_for(&self.battle.battledata, mk_sub);
-        let mk_tar = mk_tar.map(|x| self.battle.battledata.get(&x).expect(&format!("{:?} not found", x)).get_name());
-        let tar_name = mk_tar.expect(&format!("{} targeted {:?} instead of {}", mons_sub.get_name(), mk_tar, target));
+        let attack_type = self.battle.battledata.get(&mk_sub).expect("subject not in battle").get_attack_type();
+        let mk_tar = self.battle.targeting.target(&self.battle.battledata, &mk_sub, attack_type, &mut self.battle.rng);
+        let mk_tar = mk_tar.map(|x| self.battle.battledata.get(&x).unwrap_or_else(|| panic!("{:?} not found", x)).get_name());
+        let tar_name = mk_tar.unwrap_or_else(|| panic!("{} targeted {:?} instead of {}", subject, mk_tar, target));
         if tar_name != target { 
-            panic!("{} targeted {} instead of {}", mons_sub.get_name(), tar_name, target);
+            panic!("{} targeted {} instead of {}", subject, tar_name, target);
         }
     }
 
-    pub fn get_mk_and_monster_by_name(&self, name: &str) -> Option<(&MonsterKey, &Monster)>{
-        let res = self.battle.battledata.monsters.iter().find(|(mk, mons)| mons.get_name() == name);
-        res
+    pub fn get_mk_and_monster_by_name(&self, name: &str) -> Option<(MonsterKey, &Monster)>{
+        self.battle.battledata.monsters().find(|(_, mons)| mons.get_name() == name)
+    }
+
+    pub fn mk(&self, name: &str) -> MonsterKey {
+        self.get_mk_and_monster_by_name(name).unwrap_or_else(|| panic!("{} could not be found", name)).0
+    }
+
+    pub fn monster(&self, name: &str) -> &Monster {
+        self.get_mk_and_monster_by_name(name).unwrap_or_else(|| panic!("{} could not be found", name)).1
+    }
+
+    /// subject attacks target directly, skipping targeting
+    pub fn attack(&mut self, subject: &str, target: &str) -> bool {
+        let mk = self.mk(subject);
+        let tk = self.mk(target);
+        attacking::attack(&mut self.battle.battledata, &mk, &tk, &mut self.battle.rng)
+    }
+
+    /// subject takes a full turn
+    pub fn turn(&mut self, subject: &str) -> bool {
+        let mk = self.mk(subject);
+        self.battle.turn(&mk)
+    }
+
+    pub fn deal_true_damage(&mut self, name: &str, dmg: i32) {
+        let mk = self.mk(name);
+        self.battle.battledata.deal_true_damage(&mk, dmg);
+    }
+
+    pub fn assert_health(&self, name: &str, health: i32) {
+        let actual = self.monster(name).get_health();
+        if actual != health {
+            panic!("{} has {} health instead of {}", name, actual, health);
+        }
+    }
+
+    pub fn assert_armor(&self, name: &str, armor: i32) {
+        let actual = self.monster(name).get_armor();
+        if actual != armor {
+            panic!("{} has {} armor instead of {}", name, actual, armor);
+        }
+    }
+
+    pub fn assert_stunned(&self, name: &str, stunned: bool) {
+        if self.monster(name).has_effect(Ability::Stun) != stunned {
+            panic!("{} stunned should be {}", name, stunned);
+        }
     }
 }
\ No newline at end of file
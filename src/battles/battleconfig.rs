@@ -0,0 +1,69 @@
+use super::monsterspeed::{Tiebreak, GAME_TIEBREAKS};
+
+/// Engine rules that bound how long a battle can go and settle turn order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BattleConfig {
+    /// First round that ends with fatigue damage, None turns fatigue off
+    pub fatigue_start: Option<u32>,
+    /// Fatigue damage added each round, starting from this amount
+    pub fatigue_growth: i32,
+    /// Battle is a draw once this many rounds are played
+    pub max_rounds: Option<u32>,
+    /// Breaks speed ties in order before falling back to random
+    pub tiebreaks: &'static [Tiebreak],
+}
+
+impl BattleConfig {
+    /// No fatigue and no round cap, a stalled round ends the battle
+    pub fn unlimited() -> Self {
+        Self {
+            fatigue_start: None,
+            fatigue_growth: 0,
+            max_rounds: None,
+            tiebreaks: GAME_TIEBREAKS,
+        }
+    }
+
+    /// Fatigue damage dealt to every monster at the end of round_num
+    pub fn fatigue_damage(&self, round_num: u32) -> i32 {
+        match self.fatigue_start {
+            Some(start) if round_num >= start => self.fatigue_growth * (round_num - start + 1) as i32,
+            _ => 0,
+        }
+    }
+
+    /// Stalled rounds keep going when fatigue will eventually end the battle
+    pub fn fatigue_breaks_stalls(&self) -> bool {
+        self.fatigue_start.is_some() && self.fatigue_growth > 0
+    }
+
+    pub fn reached_max_rounds(&self, round_num: u32) -> bool {
+        self.max_rounds.map(|max| round_num >= max).unwrap_or(false)
+    }
+}
+
+impl Default for BattleConfig {
+    /// Fatigue from round 20 growing by 1 each round, draw at round 50
+    fn default() -> Self {
+        Self {
+            fatigue_start: Some(20),
+            fatigue_growth: 1,
+            max_rounds: Some(50),
+            tiebreaks: GAME_TIEBREAKS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BattleConfig;
+
+    #[test]
+    fn test_fatigue_damage() {
+        let config = BattleConfig::default();
+        assert_eq!(config.fatigue_damage(19), 0);
+        assert_eq!(config.fatigue_damage(20), 1);
+        assert_eq!(config.fatigue_damage(23), 4);
+        assert_eq!(BattleConfig::unlimited().fatigue_damage(100), 0);
+    }
+}
@@ -1,37 +1,66 @@
-use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
-use rand::{seq::SliceRandom, thread_rng};
+use crate::{gamedata::{deck::{Deck, MAX_MONSTERS}, monster::Monster, summoner::Summoner, statuseffect::{StatusEffect, Duration, Tick}}, cardparse::enums::{Ability, AttackType, AuraTarget, Outcome}};
 
-use crate::{gamedata::{monster::Monster, summoner::Summoner, registry::Registry}, cardparse::enums::{Ability, AttackType, Outcome}};
+use super::{death, ruleset::{Ruleset, ARMORED_UP_ARMOR, EARTHQUAKE_DAMAGE}, battleevent::BattleEvent, monsterkey::MonsterKey, setpick::SetPick};
 
-use super::{monsterkey::MonsterKey, setpick::SetPick};
+/// A team's monsters by deck slot, empty slots past the end of the deck
+pub type Team = [Option<Monster>; MAX_MONSTERS];
 
 /// Wraps database related to battle
-#[derive(Debug)]
-pub struct BattleData<'a> {
-    pub home_summ: Summoner<'a>,
-    pub oppo_summ: Summoner<'a>,
-    pub monsters: HashMap<MonsterKey, Monster<'a>>,
+#[derive(Debug, Clone)]
+pub struct BattleData {
+    pub home_summ: Summoner,
+    pub oppo_summ: Summoner,
+    /// Home then oppo, indexed by MonsterKey::team and MonsterKey::slot
+    pub teams: [Team; 2],
     pub home_alive: SetPick,
     pub oppo_alive: SetPick,
+    pub events: Option<Vec<BattleEvent>>,
+    /// Set when a speed changes mid round so the turn order can be rebuilt
+    pub speed_changed: bool,
+    pub rulesets: Vec<Ruleset>,
 }
 
-impl<'a> BattleData<'a> {
-    pub fn new(reg: &'a Registry, home: &'a Vec<&'a str>, oppo: &'a Vec<&'a str>) -> Self {
-        let (home_summ, home) = Self::vec_to_deck(reg, home);
-        let (oppo_summ, oppo) = Self::vec_to_deck(reg, oppo);
+/// Buffered events don't count, two battles at the same point with different logs are equal
+impl PartialEq for BattleData {
+    fn eq(&self, other: &Self) -> bool {
+        self.state() == other.state()
+    }
+}
+
+impl Eq for BattleData {}
+
+impl Hash for BattleData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.state().hash(state);
+    }
+}
+
+impl BattleData {
+    /// Everything compared and hashed
+    #[allow(clippy::type_complexity)]
+    fn state(&self) -> (&Summoner, &Summoner, &[Team; 2], &SetPick, &SetPick, bool, &[Ruleset]) {
+        (&self.home_summ, &self.oppo_summ, &self.teams, &self.home_alive, &self.oppo_alive, self.speed_changed, &self.rulesets)
+    }
+
+    pub fn new(home: &Deck, oppo: &Deck, rulesets: &[Ruleset]) -> Self {
+        let (home_summ, home) = Self::deck_to_team(home);
+        let (oppo_summ, oppo) = Self::deck_to_team(oppo);
         let (home_alive, oppo_alive) = Self::get_team_vecs(&home, &oppo);
-        let monsters = Self::to_map(home, oppo);
         Self {
             home_summ,
             oppo_summ,
-            monsters,
+            teams: [Self::to_team(home), Self::to_team(oppo)],
             home_alive: SetPick::new(&home_alive),
             oppo_alive: SetPick::new(&oppo_alive),
+            events: None,
+            speed_changed: false,
+            rulesets: rulesets.to_vec(),
         }
     }
 
-    pub fn get_team_vecs(home: &Vec<Monster<'a>>, oppo: &Vec<Monster<'a>>) -> (Vec<MonsterKey>, Vec<MonsterKey>) {
+    pub fn get_team_vecs(home: &Vec<Monster>, oppo: &Vec<Monster>) -> (Vec<MonsterKey>, Vec<MonsterKey>) {
         let mut home_vec = Vec::new();
         let mut oppo_vec = Vec::new();
         for mons in home {
@@ -43,31 +72,21 @@ impl<'a> BattleData<'a> {
         (home_vec, oppo_vec)
     }
 
-    pub fn vec_to_deck(reg: &'a Registry, cards: &'a Vec<&'a str>) -> (Summoner<'a>, Vec<Monster<'a>>) {
-        let mut monsters = Vec::new();
-        for (i, &key) in cards.iter().skip(1).enumerate() {
-            if let Some(carddata) = reg.map.get(key) {
-                let monster = Monster::new(carddata, i as u8);
-                monsters.push(monster);
-            } else {
-                panic!("card '{}' not found in registry", key);
-            }
-        }
-        let summ = reg.map.get(cards[0]).expect("1st card must be a summoner");
-        (Summoner::new(summ), monsters)
+    pub fn deck_to_team(deck: &Deck) -> (Summoner, Vec<Monster>) {
+        let monsters = deck.monsters.iter()
+            .enumerate()
+            .map(|(i, carddata)| Monster::new(carddata.clone(), i as u8))
+            .collect();
+        (Summoner::new(deck.summoner.clone()), monsters)
     }
 
-    pub fn to_map(home: Vec<Monster<'a>>, oppo: Vec<Monster<'a>>) -> HashMap<MonsterKey, Monster<'a>> {
-        let mut map = HashMap::new();
-        for mons in home.into_iter() {
-            let mk = MonsterKey::Home(mons.get_key() as u8);
-            map.insert(mk, mons);
-        }
-        for mons in oppo.into_iter() {
-            let mk = MonsterKey::Oppo(mons.get_key() as u8);
-            map.insert(mk, mons);
+    pub fn to_team(monsters: Vec<Monster>) -> Team {
+        let mut team: Team = Default::default();
+        for mons in monsters {
+            let slot = mons.get_key() as usize;
+            team[slot] = Some(mons);
         }
-        map
+        team
     }
 
     // pub fn get_random_alive_enemy(&self, mk: &MonsterKey) -> Option<&MonsterKey> {
@@ -78,22 +97,81 @@ impl<'a> BattleData<'a> {
     //     }
     // }
 
-    pub fn get(&self, mk: &MonsterKey) -> Option<&Monster<'a>> {
-        self.monsters.get(mk)
+    pub fn get(&self, mk: &MonsterKey) -> Option<&Monster> {
+        self.teams[mk.team()].get(mk.slot())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, mk: &MonsterKey) -> Option<&mut Monster> {
+        self.teams[mk.team()].get_mut(mk.slot())?.as_mut()
+    }
+
+    /// Every monster, dead or alive, home team first then by slot
+    pub fn monsters(&self) -> impl Iterator<Item = (MonsterKey, &Monster)> + '_ {
+        let keys = [MonsterKey::Home as fn(u8) -> MonsterKey, MonsterKey::Oppo];
+        self.teams.iter().zip(keys).flat_map(|(team, key)| {
+            team.iter().enumerate().filter_map(move |(i, mons)| Some((key(i as u8), mons.as_ref()?)))
+        })
+    }
+
+    pub fn monsters_mut(&mut self) -> impl Iterator<Item = &mut Monster> + '_ {
+        self.teams.iter_mut().flatten().flatten()
+    }
+
+    /// Alive monsters of both teams, home team first, copied so the battle can change while they are visited
+    pub fn alive(&self) -> impl Iterator<Item = MonsterKey> {
+        self.home_alive.into_iter().chain(self.oppo_alive)
+    }
+
+    /// Alive monsters on the same team as mk
+    pub fn allies(&self, mk: &MonsterKey) -> &SetPick {
+        match mk {
+            MonsterKey::Home(_) => &self.home_alive,
+            MonsterKey::Oppo(_) => &self.oppo_alive,
+        }
+    }
+
+    /// Alive monsters on the other team from mk
+    pub fn enemies(&self, mk: &MonsterKey) -> &SetPick {
+        match mk {
+            MonsterKey::Home(_) => &self.oppo_alive,
+            MonsterKey::Oppo(_) => &self.home_alive,
+        }
     }
 
     pub fn get_pos(&self, mk: &MonsterKey) -> Option<u8> {
         if self.home_alive.contains(mk) {
-            return self.home_alive.get_pos(mk).map(|x| *x as u8);
+            return self.home_alive.get_pos(mk).map(|x| x as u8);
         }
 
         if self.oppo_alive.contains(mk) {
-            return self.oppo_alive.get_pos(mk).map(|x| *x as u8);
+            return self.oppo_alive.get_pos(mk).map(|x| x as u8);
         }
 
         None
     }
 
+    /// Start buffering events, they are dropped until this is called
+    pub fn record_events(&mut self) {
+        if self.events.is_none() {
+            self.events = Some(Vec::new());
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.events.is_some()
+    }
+
+    pub fn emit(&mut self, event: BattleEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<BattleEvent> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Removes a dead monster from its team and lets the survivors react
     pub fn handle_death(&mut self, mk: &MonsterKey) {
         let monster = self.get(mk).expect("mk is not part of battle");
         // remove from home_alive / oppo_alive
@@ -103,34 +181,91 @@ impl<'a> BattleData<'a> {
 
         if self.home_alive.contains(mk) {
             self.home_alive.remove(mk);
-            return;
-        }
-
-        if self.oppo_alive.contains(mk) {
+        } else if self.oppo_alive.contains(mk) {
             self.oppo_alive.remove(mk);
+        } else {
             return;
         }
+        self.emit(BattleEvent::Death(*mk));
+        death::on_death(self, mk);
     }
 
     pub fn deal_damage(&mut self, mk: &MonsterKey, dmg: i32) {
-        let monster = self.monsters.get_mut(mk).expect("mk is not part of battle");
+        let monster = self.get_mut(mk).expect("mk is not part of battle");
         let m_armor = monster.get_armor();
         if m_armor > 0 {
             monster.set_armor(m_armor - dmg);
+            self.emit(BattleEvent::ArmorDamage { tk: *mk, dmg: dmg.min(m_armor) });
             return
         }
         let m_health = monster.get_health();
         monster.set_health(m_health - dmg);
+        self.emit(BattleEvent::HealthDamage { tk: *mk, dmg: dmg.min(m_health) });
         self.handle_death(mk)
     }
 
     pub fn deal_true_damage(&mut self, mk: &MonsterKey, dmg: i32) {
-        let monster = self.monsters.get_mut(mk).expect("mk is not part of battle");
+        let monster = self.get_mut(mk).expect("mk is not part of battle");
         let m_health = monster.get_health();
         monster.set_health(m_health - dmg);
+        self.emit(BattleEvent::HealthDamage { tk: *mk, dmg: dmg.min(m_health) });
         self.handle_death(mk)
     }
 
+    /// A stunned monster loses its turn, returns true and clears the stun if mk was stunned
+    pub fn take_stun(&mut self, mk: &MonsterKey) -> bool {
+        let monster = self.get_mut(mk).expect("mk is not part of battle");
+        if !monster.is_alive() || !monster.remove_effect(Ability::Stun) {
+            return false;
+        }
+        self.emit(BattleEvent::SkipTurn(*mk));
+        true
+    }
+
+    /// Restores up to amount health without going over max health, returns the amount restored
+    pub fn heal(&mut self, tk: &MonsterKey, amount: i32) -> i32 {
+        let target = self.get_mut(tk).expect("tk is not part of battle");
+        let health = target.get_health();
+        let healed = amount.min(target.get_max_health() - health).max(0);
+        target.set_health(health + healed);
+        healed
+    }
+
+    /// Restores up to amount armor without going over max armor, returns the amount restored
+    pub fn repair(&mut self, tk: &MonsterKey, amount: i32) -> i32 {
+        let target = self.get_mut(tk).expect("tk is not part of battle");
+        let armor = target.get_armor();
+        let repaired = amount.min(target.get_max_armor() - armor).max(0);
+        target.set_armor(armor + repaired);
+        repaired
+    }
+
+    /// True damage to every alive monster, home team first
+    pub fn deal_fatigue(&mut self, dmg: i32) {
+        self.emit(BattleEvent::Fatigue(dmg));
+        for mk in self.alive() {
+            self.deal_true_damage(&mk, dmg);
+        }
+    }
+
+    /// Melee damage to every alive monster without Flying, home team first, returns true if anyone was hit
+    pub fn deal_earthquake(&mut self) -> bool {
+        let is_grounded = |mk: &MonsterKey| !self.get(mk).expect("mk is not part of battle").has_ability(Ability::Flying);
+        let grounded = [self.home_alive.filter(is_grounded), self.oppo_alive.filter(is_grounded)];
+        if grounded.iter().all(|team| team.is_empty()) {
+            return false;
+        }
+        self.emit(BattleEvent::Earthquake(EARTHQUAKE_DAMAGE));
+        for mk in grounded.into_iter().flatten() {
+            self.deal_damage(&mk, EARTHQUAKE_DAMAGE);
+        }
+        true
+    }
+
+    pub fn has_ruleset(&self, ruleset: Ruleset) -> bool {
+        self.rulesets.contains(&ruleset)
+    }
+
     pub fn apply_summ_buffs(&mut self, mk: &MonsterKey, buffs: &[Ability]) {
         for buff in buffs {
             self.apply_summ_buff(mk, buff);
@@ -138,41 +273,29 @@ impl<'a> BattleData<'a> {
     }
  
     pub fn apply_summ_buff(&mut self, mk: &MonsterKey, buff: &Ability) {
-        let monster = self.monsters.get_mut(mk).unwrap();
+        let monster = self.get_mut(mk).unwrap();
 
         match buff {
             Ability::Health(i) => {
                 let health = monster.get_health();
-                monster.set_health_new_max(health + *i as i32);
+                let max_health = monster.get_max_health();
+                monster.set_max_health(max_health + *i as i32);
+                monster.set_health(health + *i as i32);
             },
             Ability::Armor(i) => {
                 let armor = monster.get_armor();
+                let max_armor = monster.get_max_armor();
+                monster.set_max_armor(max_armor + *i as i32);
                 monster.set_armor(armor + *i as i32);
             },
             Ability::Speed(i) => {
                 let speed = monster.get_speed();
                 monster.set_speed(speed + *i as i32);
             },
-            Ability::Melee(i) => {
-                if monster.get_attack_type() != AttackType::Melee {
-                    return;
+            Ability::Melee(i) | Ability::Ranged(i) | Ability::Magic(i) => {
+                if let Some(attack_type) = buff.damage_type() {
+                    monster.add_base_damage(attack_type, *i as i32);
                 }
-                let damage = monster.get_damage();
-                monster.set_damage(damage + *i as i32);
-            },
-            Ability::Ranged(i) => {
-                if monster.get_attack_type() != AttackType::Ranged {
-                    return;
-                }
-                let damage = monster.get_damage();
-                monster.set_damage(damage + *i as i32);
-            },
-            Ability::Magic(i) => {
-                if monster.get_attack_type() != AttackType::Magic {
-                    return;
-                }
-                let damage = monster.get_damage();
-                monster.set_damage(damage + *i as i32);
             },
             _ => (),
         }
@@ -180,20 +303,45 @@ impl<'a> BattleData<'a> {
 
     /// Register only summoner buffs of health, armor, speed, melee, ranged, magic both + and -
     pub fn register_all_team_buffs(&mut self) {
-        let home_alive: Vec<MonsterKey> = self.home_alive.iter().map(|x| x.clone()).collect();
-        let oppo_alive: Vec<MonsterKey> = self.oppo_alive.iter().map(|x| x.clone()).collect();
+        let home_alive = self.home_alive;
+        let oppo_alive = self.oppo_alive;
 
         let home_buffs = self.home_summ.get_buffs();
         let home_debuffs = self.oppo_summ.get_debuffs();
         let oppo_buffs = self.oppo_summ.get_buffs();
         let oppo_debuffs = self.home_summ.get_debuffs();
 
-        self.register_buffs_on_team(&home_alive, &home_buffs);
-        self.register_buffs_on_team(&oppo_alive, &oppo_buffs);
-        self.register_buffs_on_team(&home_alive, &home_debuffs);
-        self.register_buffs_on_team(&oppo_alive, &oppo_debuffs);
+        self.apply_rulesets();
+        self.register_buffs_on_team(home_alive.as_slice(), &home_buffs);
+        self.register_buffs_on_team(oppo_alive.as_slice(), &oppo_buffs);
+        self.register_buffs_on_team(home_alive.as_slice(), &home_debuffs);
+        self.register_buffs_on_team(oppo_alive.as_slice(), &oppo_debuffs);
 
         self.set_minimum_monster_values();
+
+        for mk in home_alive.into_iter().chain(oppo_alive) {
+            self.register_buffs(&mk);
+        }
+    }
+
+    /// Ruleset changes to abilities and stats, before any summoner buffs
+    pub fn apply_rulesets(&mut self) {
+        let back_to_basics = self.has_ruleset(Ruleset::BackToBasics);
+        let super_sneak = self.has_ruleset(Ruleset::SuperSneak);
+        let armored_up = self.has_ruleset(Ruleset::ArmoredUp);
+        let mks: Vec<MonsterKey> = self.monsters().map(|(mk, _)| mk).collect();
+        for mk in mks {
+            let monster = self.get_mut(&mk).expect("mk is not part of battle");
+            if back_to_basics {
+                monster.remove_abilities();
+            }
+            if super_sneak && monster.has_attack_type(AttackType::Melee) {
+                monster.grant_ability(Ability::Sneak);
+            }
+            if armored_up {
+                self.apply_summ_buff(&mk, &Ability::Armor(ARMORED_UP_ARMOR));
+            }
+        }
     }
 
     pub fn register_buffs_on_team(&mut self, alive: &[MonsterKey], abilities: &[Ability]) {
@@ -203,36 +351,89 @@ impl<'a> BattleData<'a> {
     }
 
     pub fn set_minimum_monster_values(&mut self) {
-        for monster in self.monsters.values_mut() {
-            if monster.get_damage() <= 0 && monster.get_default_damage() > 0 {
-                monster.set_damage(1);
-            }
-            if monster.get_health() <= 0 && monster.get_default_health() > 0 {
-                monster.set_health(1);
-            }
-            if monster.get_speed() <= 0 && monster.get_default_speed() > 0 {
-                monster.set_speed(1);
+        for monster in self.monsters_mut() {
+            monster.apply_minimums();
+        }
+    }
+
+    /// Register the auras mk provides on its allies or enemies
+    pub fn register_buffs(&mut self, mk: &MonsterKey) {
+        let provided = self.get(mk).expect("mk is not part of battle").get_buffs_provided().to_vec();
+        for ability in provided {
+            let Some((stat, aura_target)) = ability.aura_effect() else {
+                continue;
+            };
+            let targets = *match aura_target {
+                AuraTarget::Allies => self.allies(mk),
+                AuraTarget::Enemies => self.enemies(mk),
+            };
+
+            for tk in targets {
+                self.add_effect(&tk, StatusEffect::aura(stat, *mk));
             }
         }
     }
 
+    /// Takes back every aura mk put on alive monsters
+    pub fn remove_buffs(&mut self, mk: &MonsterKey) {
+        let mut removed = false;
+        for tk in self.alive() {
+            let taken = self.remove_effects(&tk, |x| x.source == Some(*mk) && x.duration == Duration::WhileSourceAlive);
+            removed |= !taken.is_empty();
+        }
+        if removed {
+            self.emit(BattleEvent::AuraRemoved(*mk));
+        }
+    }
 
-    /// Register individual buffs
-    pub fn register_buffs(&mut self, mk: &MonsterKey) {
+    /// Puts effect on mk following its stack rule, returns false if it was ignored
+    pub fn add_effect(&mut self, mk: &MonsterKey, effect: StatusEffect) -> bool {
+        let monster = self.get_mut(mk).expect("mk is not part of battle");
+        let speed = monster.get_speed();
+        let added = monster.add_effect(effect);
+        if monster.get_speed() != speed {
+            self.speed_changed = true;
+        }
+        added
+    }
 
+    /// Takes the effects matching f off mk and returns them
+    pub fn remove_effects(&mut self, mk: &MonsterKey, f: impl FnMut(&StatusEffect) -> bool) -> Vec<StatusEffect> {
+        let monster = self.get_mut(mk).expect("mk is not part of battle");
+        let speed = monster.get_speed();
+        let removed = monster.remove_effects(f);
+        if monster.get_speed() != speed {
+            self.speed_changed = true;
+        }
+        removed
+    }
+
+    /// Counts down the effects of every alive monster, home team first
+    pub fn tick_effects(&mut self, tick: Tick) {
+        for mk in self.alive() {
+            let monster = self.get_mut(&mk).expect("mk is not part of battle");
+            let speed = monster.get_speed();
+            let expired = monster.tick_effects(tick);
+            if monster.get_speed() != speed {
+                self.speed_changed = true;
+            }
+            for effect in expired {
+                self.emit(BattleEvent::EffectExpired { mk, kind: effect.kind });
+            }
+        }
     }
 
     /// Returns None if the enemies still remain
     pub fn determine_winner(&self) -> Option<Outcome> {
-        if self.home_alive.len() > 0 && self.oppo_alive.len() > 0 {
+        if !self.home_alive.is_empty() && !self.oppo_alive.is_empty() {
             return None;
         }
 
-        if self.home_alive.len() > 0 {
+        if !self.home_alive.is_empty() {
             return Some(Outcome::Win);
         }
 
-        if self.oppo_alive.len() > 0 {
+        if !self.oppo_alive.is_empty() {
             return Some(Outcome::Lose);
         }
 
@@ -242,19 +443,40 @@ impl<'a> BattleData<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{gamedata::registry::Registry, battles::monsterkey::MonsterKey};
+    use crate::{gamedata::{deck::{Deck, DeckRules}, registry::Registry}, battles::monsterkey::MonsterKey};
 
     use super::BattleData;
 
     #[test]
     fn test_get_enemy() {
         // populate battledata
-        let mut reg = Registry::from("assets/cards.csv");
+        let reg = Registry::from("assets/cards.csv");
         let home = vec!["Drake of Arnak", "Goblin Shaman", "Fire Beetle"];
         let oppo = vec!["Pyre", "Spineback Turtle", "Kobold Bruiser"];
-        let mut bd = BattleData::new(&reg, &home, &oppo);
-        let exp = vec![MonsterKey::Home(0), MonsterKey::Home(1), MonsterKey::Home(2)];
+        let _bd = BattleData::new(&Deck::with_rules(&reg, &home, DeckRules::sandbox()).unwrap(), &Deck::with_rules(&reg, &oppo, DeckRules::sandbox()).unwrap(), &[]);
+        let _exp = [MonsterKey::Home(0), MonsterKey::Home(1), MonsterKey::Home(2)];
 
         // assert_eq!(res, exp);
     }
+
+    #[test]
+    fn test_team_slots() {
+        let reg = Registry::from("assets/cards.csv");
+        let home = vec!["Drake of Arnak", "Goblin Shaman", "Fire Beetle"];
+        let oppo = vec!["Pyre", "Spineback Turtle"];
+        let mut bd = BattleData::new(&Deck::with_rules(&reg, &home, DeckRules::sandbox()).unwrap(), &Deck::with_rules(&reg, &oppo, DeckRules::sandbox()).unwrap(), &[]);
+
+        let mks: Vec<MonsterKey> = bd.monsters().map(|(mk, _)| mk).collect();
+        assert_eq!(mks, vec![MonsterKey::Home(0), MonsterKey::Home(1), MonsterKey::Oppo(0)]);
+        assert_eq!(bd.get(&MonsterKey::Home(1)).unwrap().get_name(), "Fire Beetle");
+        assert!(bd.get(&MonsterKey::Oppo(1)).is_none());
+        assert!(bd.get(&MonsterKey::Home(9)).is_none());
+
+        let beetle = MonsterKey::Home(1);
+        let health = bd.get(&beetle).unwrap().get_health();
+        bd.deal_true_damage(&beetle, health);
+        assert_eq!(bd.home_alive.as_slice(), [MonsterKey::Home(0)]);
+        assert_eq!(bd.alive().collect::<Vec<_>>(), vec![MonsterKey::Home(0), MonsterKey::Oppo(0)]);
+        assert!(!bd.get(&beetle).unwrap().is_alive());
+    }
 }
@@ -0,0 +1,55 @@
+use crate::cardparse::enums::{Ability, Outcome};
+
+use super::monsterkey::MonsterKey;
+
+/// Something that happened during a battle, in the order it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BattleEvent {
+    RoundStart(u32),
+    TurnOrder(Vec<MonsterKey>),
+    Target { mk: MonsterKey, tk: MonsterKey },
+    Miss { mk: MonsterKey, tk: MonsterKey },
+    Blast { mk: MonsterKey, tk: MonsterKey },
+    Trample { mk: MonsterKey, tk: MonsterKey },
+    Stun { mk: MonsterKey, tk: MonsterKey },
+    Thorns { mk: MonsterKey, tk: MonsterKey },
+    MagicReflect { mk: MonsterKey, tk: MonsterKey },
+    Retaliate { mk: MonsterKey, tk: MonsterKey },
+    LifeLeech { mk: MonsterKey, amount: i32 },
+    Scavenge { mk: MonsterKey, amount: i32 },
+    SkipTurn(MonsterKey),
+    ArmorDamage { tk: MonsterKey, dmg: i32 },
+    HealthDamage { tk: MonsterKey, dmg: i32 },
+    Heal { mk: MonsterKey, tk: MonsterKey, amount: i32 },
+    Repair { mk: MonsterKey, tk: MonsterKey, amount: i32 },
+    Cleanse { mk: MonsterKey, tk: MonsterKey },
+    Dispel { mk: MonsterKey, tk: MonsterKey },
+    Death(MonsterKey),
+    AuraRemoved(MonsterKey),
+    EffectExpired { mk: MonsterKey, kind: Ability },
+    Fatigue(i32),
+    Earthquake(i32),
+    Stall,
+    RoundLimit,
+    Outcome(Outcome),
+}
+
+/// Receives battle events as they are emitted
+pub trait EventSink {
+    fn emit(&mut self, event: BattleEvent);
+}
+
+impl EventSink for Vec<BattleEvent> {
+    fn emit(&mut self, event: BattleEvent) {
+        self.push(event);
+    }
+}
+
+/// Prints each event on its own line
+pub struct PrintSink;
+
+impl EventSink for PrintSink {
+    fn emit(&mut self, event: BattleEvent) {
+        println!("{:?}", event);
+    }
+}
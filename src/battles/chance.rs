@@ -0,0 +1,60 @@
+use rand::Rng;
+
+/// Where a battle's randomness comes from, an rng samples it and tourney::exact::Script enumerates it
+pub trait Chance {
+    /// True with probability p
+    fn roll(&mut self, p: f32) -> bool;
+
+    /// Index below n, each equally likely, n must be above 0
+    fn pick(&mut self, n: usize) -> usize;
+}
+
+/// Draws the same numbers rand's gen and SliceRandom would, so seeded battles don't change
+impl<R: Rng> Chance for R {
+    fn roll(&mut self, p: f32) -> bool {
+        let random: f32 = self.gen();
+        random < p
+    }
+
+    fn pick(&mut self, n: usize) -> usize {
+        self.gen_range(0..n as u32) as usize
+    }
+}
+
+impl dyn Chance + '_ {
+    /// Random element of slice, None if it's empty
+    pub fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+        if slice.is_empty() {
+            return None;
+        }
+        Some(&slice[self.pick(slice.len())])
+    }
+
+    /// Fisher-Yates, every order equally likely
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.pick(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use super::Chance;
+
+    #[test]
+    fn test_matches_rand() {
+        let mut ours = StdRng::seed_from_u64(5);
+        let mut theirs = StdRng::seed_from_u64(5);
+        let mut slice: Vec<u8> = (0..9).collect();
+        let mut expected = slice.clone();
+        for _ in 0..20 {
+            (&mut ours as &mut dyn Chance).shuffle(&mut slice);
+            expected.shuffle(&mut theirs);
+            assert_eq!(slice, expected);
+            assert_eq!((&mut ours as &mut dyn Chance).choose(&slice), slice.choose(&mut theirs));
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::cardparse::enums::Ability;
+
+use super::{battledata::BattleData, battleevent::BattleEvent, monsterkey::MonsterKey};
+
+/// Max health a Scavenger gains each time a monster dies
+pub const SCAVENGE_AMOUNT: i32 = 1;
+
+/// A survivor's reaction to a death, called as hook(bd, survivor, dead)
+pub type DeathHook = fn(&mut BattleData, &MonsterKey, &MonsterKey);
+
+/// Reactions every surviving monster gets, run in this order
+pub const DEATH_HOOKS: &[DeathHook] = &[scavenge];
+
+/// Runs once a monster has been removed from the alive sets.
+/// The dead monster's own effects go first, then survivors react home team first, front to back
+pub fn on_death(bd: &mut BattleData, dk: &MonsterKey) {
+    last_words(bd, dk);
+
+    for sk in bd.alive() {
+        for hook in DEATH_HOOKS {
+            // an earlier reaction may have killed this survivor
+            if !bd.get(&sk).expect("sk not in bd").is_alive() {
+                break;
+            }
+            hook(bd, &sk, dk);
+        }
+    }
+}
+
+/// Effects that belong to the monster that died, like the auras it was providing
+pub fn last_words(bd: &mut BattleData, dk: &MonsterKey) {
+    bd.remove_buffs(dk);
+}
+
+/// Scavenger gains max health and health whenever any monster dies
+pub fn scavenge(bd: &mut BattleData, sk: &MonsterKey, _dk: &MonsterKey) {
+    let monster = bd.get_mut(sk).expect("sk not in bd");
+    if !monster.has_ability(Ability::Scavenger) {
+        return;
+    }
+    monster.gain_max_health(SCAVENGE_AMOUNT);
+    bd.emit(BattleEvent::Scavenge { mk: *sk, amount: SCAVENGE_AMOUNT });
+}
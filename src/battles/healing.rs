@@ -0,0 +1,90 @@
+use crate::cardparse::enums::Ability;
+
+use super::{battledata::BattleData, battleevent::BattleEvent, monsterkey::MonsterKey};
+
+/// Amount of armor restored by Repair
+pub const REPAIR_AMOUNT: i32 = 2;
+
+/// Heal and Tank Heal restore 1/3 of max health rounded down, at least 2
+pub fn heal_amount(max_health: i32) -> i32 {
+    (max_health / 3).max(2)
+}
+
+/// Runs the healing family of abilities at the start of mk's turn, in the order heal, tank heal, repair, cleanse
+pub fn heal_turn(bd: &mut BattleData, mk: &MonsterKey) {
+    let has = |bd: &BattleData, ability| bd.get(mk).expect("mk not in bd").has_ability(ability);
+    if !bd.get(mk).expect("mk not in bd").is_alive() {
+        return;
+    }
+
+    if has(bd, Ability::Heal) {
+        heal_self(bd, mk);
+    }
+    if has(bd, Ability::TankHeal) {
+        tank_heal(bd, mk);
+    }
+    if has(bd, Ability::Repair) {
+        repair(bd, mk);
+    }
+    if has(bd, Ability::Cleanse) {
+        cleanse(bd, mk);
+    }
+}
+
+pub fn heal_self(bd: &mut BattleData, mk: &MonsterKey) {
+    let monster = bd.get(mk).expect("mk not in bd");
+    let amount = heal_amount(monster.get_max_health());
+    let healed = bd.heal(mk, amount);
+    if healed > 0 {
+        bd.emit(BattleEvent::Heal { mk: *mk, tk: *mk, amount: healed });
+    }
+}
+
+/// Heals the ally in first position
+pub fn tank_heal(bd: &mut BattleData, mk: &MonsterKey) {
+    let tk = bd.allies(mk).index(0);
+    let target = bd.get(&tk).expect("tk not in bd");
+    let amount = heal_amount(target.get_max_health());
+    let healed = bd.heal(&tk, amount);
+    if healed > 0 {
+        bd.emit(BattleEvent::Heal { mk: *mk, tk, amount: healed });
+    }
+}
+
+/// Repairs the ally whose armor has taken the most damage, ties go to the earlier position
+pub fn repair(bd: &mut BattleData, mk: &MonsterKey) {
+    let tk = bd.allies(mk).iter().fold((0, None), |acc, elem| {
+        let mons = bd.get(elem).unwrap();
+        let missing = mons.get_max_armor() - mons.get_armor();
+        if missing > acc.0 {
+            return (missing, Some(*elem));
+        }
+        acc
+    }).1;
+
+    if let Some(tk) = tk {
+        let repaired = bd.repair(&tk, REPAIR_AMOUNT);
+        bd.emit(BattleEvent::Repair { mk: *mk, tk, amount: repaired });
+    }
+}
+
+/// Removes negative effects from the ally in first position
+pub fn cleanse(bd: &mut BattleData, mk: &MonsterKey) {
+    let tk = bd.allies(mk).index(0);
+    if bd.get_mut(&tk).expect("tk not in bd").cleanse().is_empty() {
+        return;
+    }
+    bd.emit(BattleEvent::Cleanse { mk: *mk, tk });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::heal_amount;
+
+    #[test]
+    fn test_heal_amount() {
+        assert_eq!(heal_amount(3), 2);
+        assert_eq!(heal_amount(7), 2);
+        assert_eq!(heal_amount(10), 3);
+    }
+}
@@ -1,10 +1,15 @@
 pub mod battle;
 pub mod battledata;
-pub mod roundrobin;
 pub mod roundrobiniter;
 pub mod monsterkey;
 pub mod monsterspeed;
 pub mod targeting;
+pub mod targetingpolicy;
 pub mod attacking;
+pub mod healing;
+pub mod death;
 pub mod setpick;
-pub mod battlechecker;
\ No newline at end of file
+pub mod battlechecker;
+pub mod battleevent;
+pub mod battleconfig;
+pub mod ruleset;pub mod chance;
@@ -4,3 +4,20 @@ pub enum MonsterKey {
     Home(u8),
     Oppo(u8),
 }
+
+impl MonsterKey {
+    /// Index of the team in BattleData's per-team arrays, home is 0
+    pub fn team(&self) -> usize {
+        match self {
+            MonsterKey::Home(_) => 0,
+            MonsterKey::Oppo(_) => 1,
+        }
+    }
+
+    /// Position in the deck the monster started at
+    pub fn slot(&self) -> usize {
+        match *self {
+            MonsterKey::Home(i) | MonsterKey::Oppo(i) => i as usize,
+        }
+    }
+}
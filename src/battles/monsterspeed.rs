@@ -1,39 +1,121 @@
 use std::cmp::Ordering;
 
-use crate::gamedata::{registry::Registry, monster::Monster};
+use crate::{cardparse::enums::AttackType, gamedata::monster::Monster};
 
-use super::{monsterkey::MonsterKey, battledata::BattleData, roundrobin::RoundRobin};
+use super::{monsterkey::MonsterKey, battledata::BattleData, ruleset::Ruleset, roundrobiniter::TurnKey};
 
-// A class for bin heap
-#[derive(Debug, Clone)]
+/// What breaks a speed tie, checked in order before falling back to random
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Tiebreak {
+    /// Magic, then ranged, then melee
+    AttackType,
+    /// Rarer cards first
+    Rarity,
+    /// Higher levels first
+    Level,
+}
+
+/// The live game's tiebreak order without rarity, which the shipped card sets don't have a column for.
+/// Card sets with rarities can put Tiebreak::Rarity between the 2 in BattleConfig::tiebreaks
+pub const GAME_TIEBREAKS: &[Tiebreak] = &[Tiebreak::AttackType, Tiebreak::Level];
+
+/// Most tiebreaks a turn order can use, one per kind
+pub const MAX_TIEBREAKS: usize = 3;
+
+/// One rank per tiebreak in order, unused ones are 0 so they never break a tie
+pub type Ranks = [u8; MAX_TIEBREAKS];
+
+impl Tiebreak {
+    /// Higher ranks go first
+    pub fn rank(&self, mons: &Monster) -> u8 {
+        match self {
+            Tiebreak::AttackType => match mons.get_attack_type() {
+                AttackType::Magic => 3,
+                AttackType::Ranged => 2,
+                AttackType::Melee => 1,
+                AttackType::None => 0,
+            },
+            Tiebreak::Rarity => mons.get_rarity() as u8,
+            Tiebreak::Level => mons.get_level(),
+        }
+    }
+}
+
+/// A monster's place in the turn order
+#[derive(Debug, Clone, Copy)]
 pub struct MonsterSpeed {
     pub mk: MonsterKey,
     pub speed: u8,
+    /// One rank per tiebreak, compared after speed
+    pub ranks: Ranks,
 }
 
 
 impl MonsterSpeed {
     pub fn new(mk: MonsterKey, speed: u8) -> Self {
+        Self::with_ranks(mk, speed, &[])
+    }
+
+    /// Ranks past MAX_TIEBREAKS are ignored
+    pub fn with_ranks(mk: MonsterKey, speed: u8, ranks: &[u8]) -> Self {
+        let mut fixed = [0; MAX_TIEBREAKS];
+        for (rank, given) in fixed.iter_mut().zip(ranks) {
+            *rank = *given;
+        }
         Self {
             mk,
+            speed,
+            ranks: fixed,
+        }
+    }
+
+    fn from_monster(bd: &BattleData, mk: &MonsterKey, mons: &Monster, tiebreaks: &[Tiebreak]) -> Self {
+        let mut ranks = [0; MAX_TIEBREAKS];
+        for (rank, tiebreak) in ranks.iter_mut().zip(tiebreaks) {
+            *rank = tiebreak.rank(mons);
+        }
+        Self {
+            mk: *mk,
+            speed: Self::turn_speed(bd, mons),
+            ranks,
+        }
+    }
+
+    /// Alive monsters sorted by key, seeded battles repeat because the order never changes
+    pub fn alive<'a>(bd: &'a BattleData, tiebreaks: &'a [Tiebreak]) -> impl Iterator<Item = Self> + 'a {
+        bd.monsters()
+        .filter(|(_, mons)| mons.get_health() > 0)
+        .map(move |(mk, mons)| Self::from_monster(bd, &mk, mons, tiebreaks))
+    }
+
+    pub fn get_vec(bd: &BattleData, tiebreaks: &[Tiebreak]) -> Vec<Self> {
+        Self::alive(bd, tiebreaks).collect()
+    }
+
+    /// Speed used for turn order, Reverse Speed lets the slowest go first
+    pub fn turn_speed(bd: &BattleData, mons: &Monster) -> u8 {
+        let speed = mons.get_speed() as u8;
+        if bd.has_ruleset(Ruleset::ReverseSpeed) {
+            u8::MAX - speed
+        } else {
             speed
         }
     }
 
-    pub fn get_vec(bd: &BattleData) -> Vec<Self> {
-        let vec = bd.monsters
-        .iter()
-        .filter(|(mk, mons)| mons.get_health() > 0)
-        .map(|(mk, mons)| {
-            Self::new(*mk, mons.get_speed() as u8)
-        }).collect();
-        vec
+    /// Same as alive but only for the given keys
+    pub fn alive_in<'a>(bd: &'a BattleData, mks: &'a [MonsterKey], tiebreaks: &'a [Tiebreak]) -> impl Iterator<Item = Self> + 'a {
+        Self::alive(bd, tiebreaks).filter(|ms| mks.contains(&ms.mk))
+    }
+
+    /// Monsters with equal keys are tied and go in random order
+    pub fn turn_key(&self) -> TurnKey {
+        (self.speed, self.ranks)
     }
 }
 
 impl Ord for MonsterSpeed {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.speed.cmp(&other.speed)
+        self.speed.cmp(&other.speed).then_with(|| self.ranks.cmp(&other.ranks))
     }
 }
 
@@ -45,7 +127,7 @@ impl PartialOrd for MonsterSpeed {
 
 impl PartialEq for MonsterSpeed {
     fn eq(&self, other: &Self) -> bool {
-        self.speed == other.speed
+        self.speed == other.speed && self.ranks == other.ranks
     }
 }
 
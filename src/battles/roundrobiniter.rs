@@ -1,77 +1,70 @@
-use std::collections::{BinaryHeap, HashMap, HashSet, LinkedList};
+use crate::gamedata::deck::MAX_MONSTERS;
 
-use rand::seq::SliceRandom;
-use rand::{thread_rng, Rng};
+use super::{chance::Chance, monsterkey::MonsterKey, monsterspeed::{MonsterSpeed, Ranks}};
 
-use super::monsterspeed::MonsterSpeed;
+/// Speed then tiebreak ranks, see MonsterSpeed::turn_key
+pub type TurnKey = (u8, Ranks);
 
-// must make K into int or make V into K
+/// Both teams' monsters
+pub const MAX_TURNS: usize = 2 * MAX_MONSTERS;
+
+/// Hands out monsters fastest first, tied monsters in a random order, without allocating
 pub struct RoundRobinIter {
-    heap: BinaryHeap<u8>,
-    dict: HashMap<u8, Vec<MonsterSpeed>>,
+    /// Slowest first so the next turn is popped off the end
+    order: [MonsterSpeed; MAX_TURNS],
+    len: usize,
 }
 
 impl RoundRobinIter {
-    pub fn new(values: Vec<MonsterSpeed>) -> Self {
-        let mut map = Self::make_map(&values);
-        Self::shuffle_map(&mut map);
-        let mut heap = Self::make_heap(&map);
-        // println!("map: {:?}\nheap: {:?}", map, heap);
-        Self {
-            heap,
-            dict: map,
+    /// Values past MAX_TURNS are ignored
+    pub fn new(values: impl IntoIterator<Item = MonsterSpeed>, rng: &mut dyn Chance) -> Self {
+        let mut rr = Self {
+            order: [MonsterSpeed::new(MonsterKey::Home(0), 0); MAX_TURNS],
+            len: 0,
+        };
+        for value in values.into_iter().take(MAX_TURNS) {
+            rr.order[rr.len] = value;
+            rr.len += 1;
         }
+        rr.sort();
+        rr.shuffle_ties(rng);
+        rr
     }
 
-    pub fn make_heap(map: &HashMap<u8, Vec<MonsterSpeed>>) -> BinaryHeap<u8> {
-        let mut arr: Vec<u8> = map.keys().map(|x|x.clone()).collect();
-        BinaryHeap::from(arr)
-    }
-
-    pub fn make_map(values: &Vec<MonsterSpeed>) -> HashMap<u8, Vec<MonsterSpeed>> {
-        let mut map = HashMap::new();
-        for key in values {
-            if !map.contains_key(&key.speed) {
-                map.insert(key.speed, Vec::new());
+    /// Stable insertion sort by turn key, there are too few monsters for anything fancier
+    fn sort(&mut self) {
+        let order = &mut self.order[..self.len];
+        for i in 1..order.len() {
+            let mut j = i;
+            while j > 0 && order[j - 1].turn_key() > order[j].turn_key() {
+                order.swap(j - 1, j);
+                j -= 1;
             }
-            let cards = map.get_mut(&key.speed).unwrap();
-            cards.push(key.clone());
         }
-        map
     }
 
-    pub fn shuffle_map(map: &mut HashMap<u8, Vec<MonsterSpeed>>) {
-        let mut rng = thread_rng();
-        for arr in map.values_mut() {
-            arr.shuffle(&mut rng);
+    /// Shuffles each group of tied monsters, slowest group first so a seeded rng gives the same result
+    fn shuffle_ties(&mut self, rng: &mut dyn Chance) {
+        let order = &mut self.order[..self.len];
+        let mut start = 0;
+        while start < order.len() {
+            let key = order[start].turn_key();
+            let end = start + order[start..].iter().take_while(|ms| ms.turn_key() == key).count();
+            rng.shuffle(&mut order[start..end]);
+            start = end;
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        return self.heap.is_empty();
+        self.len == 0
     }
 
     pub fn pop(&mut self) -> Option<MonsterSpeed> {
-        if self.heap.is_empty() {
+        if self.len == 0 {
             return None;
         }
-        let key = self.heap.peek().unwrap();
-        if !self.dict.contains_key(key) {
-            panic!("heap error, contains {:?} not in {:?}", key, self.dict);
-        }
-        let arr = self.dict.get_mut(key).unwrap();
-        // println!("{}: {:?}", key, arr);
-        if arr.is_empty() {
-            panic!("heap key corresponds to empty list");
-            // return None;
-        }
-
-        let res = arr.pop().unwrap();
-        // println!("res: {:?}", res);
-        if arr.is_empty() {
-            self.heap.pop();
-        }
-        Some(res)
+        self.len -= 1;
+        Some(self.order[self.len])
     }
 }
 
@@ -85,7 +78,11 @@ impl Iterator for RoundRobinIter {
 
 #[cfg(test)]
 mod tests {
-    use crate::battles::monsterkey::MonsterKey;
+    use std::collections::HashSet;
+
+    use rand::{rngs::StdRng, thread_rng, SeedableRng};
+
+    use crate::{battles::{battledata::BattleData, monsterkey::MonsterKey, monsterspeed::{Tiebreak, GAME_TIEBREAKS}}, gamedata::{deck::{Deck, DeckRules}, registry::Registry}};
 
     use super::*;
 
@@ -102,7 +99,7 @@ mod tests {
             MonsterSpeed::new(MonsterKey::Oppo(3), 4),
         ];
 
-        let rr = RoundRobinIter::new(input);
+        let rr = RoundRobinIter::new(input, &mut thread_rng());
         let mut res = Vec::new();
         for val in rr {
             res.push(val.speed);
@@ -117,5 +114,38 @@ mod tests {
 
         let exp = vec![8,8,8,7,4,3,2,2];
         assert_eq!(res, exp);
+
+        // speed ties go by attack type, then rarity, then level, then random
+        let input = vec![
+            MonsterSpeed::with_ranks(MonsterKey::Home(0), 8, &[1, 2, 1]),
+            MonsterSpeed::with_ranks(MonsterKey::Home(1), 8, &[3, 0, 1]),
+            MonsterSpeed::with_ranks(MonsterKey::Home(2), 8, &[1, 2, 3]),
+            MonsterSpeed::with_ranks(MonsterKey::Oppo(0), 8, &[2, 0, 1]),
+            MonsterSpeed::with_ranks(MonsterKey::Oppo(1), 8, &[1, 0, 1]),
+            MonsterSpeed::with_ranks(MonsterKey::Oppo(2), 8, &[1, 0, 1]),
+            MonsterSpeed::with_ranks(MonsterKey::Oppo(3), 9, &[0, 0, 1]),
+        ];
+        let mut last_two = HashSet::new();
+        for seed in 0..20 {
+            let res: Vec<MonsterKey> = RoundRobinIter::new(input.clone(), &mut StdRng::seed_from_u64(seed)).map(|ms| ms.mk).collect();
+            assert_eq!(res[..5], [MonsterKey::Oppo(3), MonsterKey::Home(1), MonsterKey::Oppo(0), MonsterKey::Home(2), MonsterKey::Home(0)]);
+            last_two.insert(res[5]);
+        }
+        assert_eq!(last_two.len(), 2);
+    }
+
+    #[test]
+    fn test_tiebreak_ranks() {
+        let reg = Registry::from("assets/leveled_cards.csv");
+        let home = vec!["Pyre", "Goblin Fireballer", "Kobold Bruiser:3"];
+        let oppo = vec!["Contessa L'ament", "Riftwing:2"];
+        let bd = BattleData::new(&Deck::with_rules(&reg, &home, DeckRules::sandbox()).unwrap(), &Deck::with_rules(&reg, &oppo, DeckRules::sandbox()).unwrap(), &[]);
+
+        let ranks: Vec<Ranks> = MonsterSpeed::get_vec(&bd, GAME_TIEBREAKS).into_iter().map(|ms| ms.ranks).collect();
+        assert_eq!(ranks, vec![[2, 1, 0], [1, 3, 0], [0, 2, 0]]);
+        let with_rarity = &[Tiebreak::AttackType, Tiebreak::Rarity, Tiebreak::Level];
+        let ranks: Vec<Ranks> = MonsterSpeed::get_vec(&bd, with_rarity).into_iter().map(|ms| ms.ranks).collect();
+        assert_eq!(ranks, vec![[2, 0, 1], [1, 0, 3], [0, 2, 2]]);
+        assert!(MonsterSpeed::get_vec(&bd, &[]).iter().all(|ms| ms.ranks == [0; 3]));
     }
 }
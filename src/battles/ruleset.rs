@@ -0,0 +1,131 @@
+use std::fmt::Display;
+
+use crate::cardparse::enums::AttackType;
+
+use super::{chance::Chance, battledata::BattleData, monsterkey::MonsterKey, targeting, targetingpolicy::TargetingPolicy};
+
+/// Damage Earthquake does to every monster without Flying at the end of each round
+pub const EARTHQUAKE_DAMAGE: i32 = 2;
+
+/// Armor every monster gets from Armored Up
+pub const ARMORED_UP_ARMOR: i8 = 2;
+
+/// Match rules that change how the battle plays out
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Ruleset {
+    /// Non flying monsters take melee damage at the end of each round
+    Earthquake,
+    /// Slowest monsters go first and speed helps the slower side evade
+    ReverseSpeed,
+    /// Every monster gets extra armor
+    ArmoredUp,
+    /// Monsters lose all of their abilities
+    BackToBasics,
+    /// Every melee monster has Sneak
+    SuperSneak,
+    /// Melee monsters can attack from any position
+    MeleeMayhem,
+}
+
+impl Ruleset {
+    pub const ALL: [Ruleset; 6] = [
+        Self::Earthquake,
+        Self::ReverseSpeed,
+        Self::ArmoredUp,
+        Self::BackToBasics,
+        Self::SuperSneak,
+        Self::MeleeMayhem,
+    ];
+
+    /// Parses a comma separated list like "earthquake,super sneak"
+    pub fn parse_list(string: &str) -> Result<Vec<Ruleset>, UnknownRuleset> {
+        string.split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(Ruleset::try_from)
+            .collect()
+    }
+}
+
+impl Display for Ruleset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Earthquake => "earthquake",
+            Self::ReverseSpeed => "reverse speed",
+            Self::ArmoredUp => "armored up",
+            Self::BackToBasics => "back to basics",
+            Self::SuperSneak => "super sneak",
+            Self::MeleeMayhem => "melee mayhem",
+        })
+    }
+}
+
+impl TryFrom<&str> for Ruleset {
+    type Error = UnknownRuleset;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Self::ALL.into_iter()
+            .find(|ruleset| ruleset.to_string() == string)
+            .ok_or_else(|| UnknownRuleset { name: string.to_owned() })
+    }
+}
+
+/// A ruleset name that isn't one of Ruleset::ALL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownRuleset {
+    pub name: String,
+}
+
+impl Display for UnknownRuleset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<String> = Ruleset::ALL.iter().map(|x| x.to_string()).collect();
+        write!(f, "ruleset '{}' not found, the rulesets are {}", self.name, names.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownRuleset {}
+
+/// Melee Mayhem, melee monsters that can't reach anyone hit the front enemy, taunt still applies
+#[derive(Debug)]
+pub struct MeleeMayhemTargeting {
+    pub inner: Box<dyn TargetingPolicy>,
+}
+
+impl MeleeMayhemTargeting {
+    pub fn new(inner: impl TargetingPolicy + 'static) -> Self {
+        Self {
+            inner: Box::new(inner),
+        }
+    }
+}
+
+impl TargetingPolicy for MeleeMayhemTargeting {
+    fn target(&self, bd: &BattleData, mk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> Option<MonsterKey> {
+        let tk = self.inner.target(bd, mk, attack_type, rng);
+        if tk.is_some() {
+            return tk;
+        }
+        let monster = bd.get(mk).expect("mk is not in battle");
+        if !monster.is_alive() || attack_type != AttackType::Melee || bd.enemies(mk).is_empty() {
+            return None;
+        }
+        targeting::check_taunt(bd, mk, rng).or_else(|| targeting::target_first_pos(bd, mk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(Ruleset::parse_list("earthquake, super sneak"), Ok(vec![Ruleset::Earthquake, Ruleset::SuperSneak]));
+        assert_eq!(Ruleset::parse_list(""), Ok(vec![]));
+        let err = Ruleset::parse_list("earthquake,erthquake").unwrap_err();
+        assert_eq!(err, UnknownRuleset { name: "erthquake".to_owned() });
+        assert!(err.to_string().contains("earthquake, reverse speed, armored up"));
+        for ruleset in Ruleset::ALL {
+            assert_eq!(Ruleset::try_from(ruleset.to_string().as_str()), Ok(ruleset));
+        }
+    }
+}
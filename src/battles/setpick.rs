@@ -1,80 +1,100 @@
-use std::{collections::{HashSet, HashMap}, slice::Iter, vec::IntoIter};
+use std::hash::{Hash, Hasher};
 
-use rand::{rngs::ThreadRng, seq::SliceRandom, thread_rng};
+use crate::{cardparse::enums::Ability, gamedata::deck::MAX_MONSTERS};
 
-use crate::cardparse::enums::Ability;
+use super::{chance::Chance, monsterkey::MonsterKey, battledata::BattleData};
 
-use super::{monsterkey::MonsterKey, battledata::BattleData};
-
-/// Pick from a set like it's a vec but with the performance of a set
-/// Must keep track of positions like a vec
-#[derive(Debug)]
+/// Alive monsters of a team in position order, kept in a fixed array so it never allocates
+/// At most 6 keys so lookups are a scan, which beats hashing at this size
+#[derive(Debug, Clone, Copy)]
 pub struct SetPick {
-    keys: Vec<MonsterKey>,
-    map: HashMap<MonsterKey, usize>,
+    keys: [MonsterKey; MAX_MONSTERS],
+    len: usize,
 }
 
 impl SetPick {
     pub fn new(mks: &[MonsterKey]) -> Self {
-        let mut keys = Vec::new();
-        let mut map = HashMap::new();
-        for i in 0..mks.len() {
-            keys.push(mks[i]);
-            map.insert(mks[i], i);
-        }
-        Self {
-            keys,
-            map
+        let mut set = Self {
+            keys: [MonsterKey::Home(0); MAX_MONSTERS],
+            len: 0,
+        };
+        for mk in mks {
+            set.push(*mk);
         }
+        set
+    }
+
+    fn push(&mut self, mk: MonsterKey) {
+        self.keys[self.len] = mk;
+        self.len += 1;
     }
 
     pub fn len(&self) -> usize {
-        self.keys.len()
+        self.len
+    }
+
+    pub fn as_slice(&self) -> &[MonsterKey] {
+        &self.keys[..self.len]
     }
 
     pub fn contains(&self, mk: &MonsterKey) -> bool {
-        if self.map.contains_key(mk) {
-            return true;
-        }
-        false
+        self.as_slice().contains(mk)
     }
 
     pub fn remove(&mut self, mk: &MonsterKey) {
-        if !self.map.contains_key(mk) {
+        let Some(index) = self.get_pos(mk) else {
             return
-        }
-
-        let &index = self.map.get(mk).unwrap();
-        self.keys.remove(index);
-        self.map.remove(mk);
-        for i in index..self.keys.len() {
-            let new_mk = self.keys[i];
-            self.map.insert(new_mk, i);
-        }
+        };
+        self.keys.copy_within(index + 1..self.len, index);
+        self.len -= 1;
     }
 
-    pub fn choose(&self, rng: &mut ThreadRng) -> Option<&MonsterKey> {
-        self.keys.choose(rng)
+    pub fn choose(&self, rng: &mut dyn Chance) -> Option<&MonsterKey> {
+        rng.choose(self.as_slice())
     }
 
-    pub fn get_pos(&self, mk: &MonsterKey) -> Option<&usize> {
-        self.map.get(mk)
+    pub fn get_pos(&self, mk: &MonsterKey) -> Option<usize> {
+        self.as_slice().iter().position(|x| x == mk)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.keys.is_empty()
+        self.len == 0
     }
 
     pub fn index(&self, i: usize) -> MonsterKey {
-        self.keys[i]
+        self.as_slice()[i]
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &MonsterKey> {
-        self.keys.iter()
+        self.as_slice().iter()
+    }
+
+    /// Keys directly before and after mk
+    pub fn neighbours(&self, mk: &MonsterKey) -> SetPick {
+        let mut res = SetPick::new(&[]);
+        let Some(pos) = self.get_pos(mk) else {
+            return res;
+        };
+        if pos > 0 {
+            res.push(self.keys[pos - 1]);
+        }
+        if pos + 1 < self.len {
+            res.push(self.keys[pos + 1]);
+        }
+        res
+    }
+
+    /// Keys matching f, in order
+    pub fn filter(&self, f: impl Fn(&MonsterKey) -> bool) -> SetPick {
+        let mut res = SetPick::new(&[]);
+        for mk in self.iter().filter(|x| f(x)) {
+            res.push(*mk);
+        }
+        res
     }
 
     pub fn least_health(&self, bd: &BattleData) -> Option<MonsterKey> {
-        self.keys.iter().fold((i32::MAX, None), |mut acc, elem| {
+        self.iter().fold((i32::MAX, None), |mut acc, elem| {
             let mons = bd.get(elem).unwrap();
             let health = mons.get_health();
             if health < acc.0 {
@@ -84,35 +104,27 @@ impl SetPick {
         }).1
     }
 
-    pub fn random_from_filter(&self, f: impl Fn(&MonsterKey) -> bool) -> Option<MonsterKey> {
-        let filtered: Vec<_> = self.keys.iter().filter(|x| f(x)).collect();
-        filtered.choose(&mut thread_rng()).copied().copied()
+    pub fn random_from_filter(&self, f: impl Fn(&MonsterKey) -> bool, rng: &mut dyn Chance) -> Option<MonsterKey> {
+        self.filter(f).choose(rng).copied()
     }
 
     pub fn first_from_filter(&self, f: impl FnMut(&&MonsterKey) -> bool) -> Option<MonsterKey> {
-        self.keys.iter().find(f).copied()
+        self.iter().find(f).copied()
     }
 
-    pub fn get_taunt(&self, bd: &BattleData) -> Option<MonsterKey> {
-        let filtered: Vec<_> = self.keys.iter().filter(|x| {
-            let mons = bd.get(x);
-            if mons.is_none() {
-                return true;
-            }
-            mons.unwrap().has_ability(Ability::Taunt)
-        }).collect();
-        filtered.choose(&mut thread_rng()).copied().copied()
+    pub fn get_taunt(&self, bd: &BattleData, rng: &mut dyn Chance) -> Option<MonsterKey> {
+        self.random_from_filter(|x| bd.get(x).is_none_or(|mons| mons.has_ability(Ability::Taunt)), rng)
     }
 
     pub fn to_monster_string(&self, bd: &BattleData) -> String {
-        let mons: Vec<_> = self.keys.iter().map(|x| bd.get(x).unwrap()).collect();
+        let mons: Vec<_> = self.iter().map(|x| bd.get(x).unwrap()).collect();
         let strings: Vec<_> = mons.iter().map(|x| {
             let mut string = format!("{} ({}/{})",
                 x.get_name(),
                 x.get_health(),
                 x.get_max_health(),
             );
-            
+
             if x.get_armor() > 0 {
                 string.push_str(&format!(" [{}]", x.get_armor()));
             }
@@ -126,26 +138,51 @@ impl SetPick {
 
 }
 
+impl PartialEq for SetPick {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for SetPick {}
+
+impl Hash for SetPick {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state);
+    }
+}
+
+/// Copies the keys out so the battle data can change while they are visited
+impl IntoIterator for SetPick {
+    type Item = MonsterKey;
+    type IntoIter = std::iter::Take<std::array::IntoIter<MonsterKey, MAX_MONSTERS>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.keys.into_iter().take(self.len)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{battles::monsterkey::MonsterKey, gamedata::monster::Monster};
+    use crate::battles::monsterkey::MonsterKey;
 
     use super::SetPick;
 
     #[test]
     pub fn test_pop_from_center() {
-        let arr = (1..4).map(|x| MonsterKey::Home(x)).collect::<Vec<_>>();
+        let arr = (1..4).map(MonsterKey::Home).collect::<Vec<_>>();
         let mut alive = SetPick::new(&arr);
         let key = MonsterKey::Home(2);
         // println!("{:?}", alive);
         let exp = vec![MonsterKey::Home(1), MonsterKey::Home(3)];
         alive.remove(&key);
-        assert_eq!(alive.keys, exp);
+        assert_eq!(alive.as_slice(), exp);
+        assert_eq!(alive.get_pos(&MonsterKey::Home(3)), Some(1));
         // println!("{:?}", alive);
         let key = MonsterKey::Home(3);
         let exp = vec![MonsterKey::Home(1)];
         alive.remove(&key);
-        assert_eq!(alive.keys, exp);
+        assert_eq!(alive.as_slice(), exp);
         // println!("{:?}", alive);
     }
 
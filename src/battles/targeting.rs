@@ -1,41 +1,46 @@
-use rand::{thread_rng, seq::SliceRandom};
-
 use crate::cardparse::enums::{AttackType, Ability};
 
-use super::{battledata::BattleData, monsterkey::MonsterKey};
+use super::{chance::Chance, battledata::BattleData, monsterkey::MonsterKey};
+
+/// Target for mk's first attack
+pub fn target_for(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
+    let attack_type = bd.get(mk).unwrap_or_else(|| panic!("{:?} not in battledata", mk)).get_attack_type();
+    target_for_type(bd, mk, attack_type, rng)
+}
 
-pub fn target_for(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
+/// Target for mk's attack of attack_type, each attack type has its own rules
+pub fn target_for_type(bd: &BattleData, mk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> Option<MonsterKey> {
     // initial checks
     if bd.oppo_alive.is_empty() || bd.home_alive.is_empty() {
         return None;
     }
 
-    let monster = bd.get(mk).expect(&format!("{:?} not in battledata", mk));
+    let monster = bd.get(mk).unwrap_or_else(|| panic!("{:?} not in battledata", mk));
     if !monster.is_alive() {
         return None;
     }
 
     // match the type of attack
-    match monster.get_attack_type() {
-        AttackType::Melee => target_for_melee(bd, mk),
-        AttackType::Ranged => target_for_ranged(bd, mk),
-        AttackType::Magic => target_for_magic(bd, mk),
+    match attack_type {
+        AttackType::Melee => target_for_melee(bd, mk, rng),
+        AttackType::Ranged => target_for_ranged(bd, mk, rng),
+        AttackType::Magic => target_for_magic(bd, mk, rng),
         AttackType::None => None,
     }
 }
 
-pub fn check_taunt(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
+pub fn check_taunt(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
     match mk {
-        MonsterKey::Home(_) => bd.oppo_alive.get_taunt(bd),
-        MonsterKey::Oppo(_) => bd.home_alive.get_taunt(bd)
+        MonsterKey::Home(_) => bd.oppo_alive.get_taunt(bd, rng),
+        MonsterKey::Oppo(_) => bd.home_alive.get_taunt(bd, rng)
     }
 }
 
-pub fn target_for_sneak(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
+pub fn target_for_sneak(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
     let oppo_last = bd.oppo_alive.len() - 1;
     let home_last = bd.home_alive.len() - 1;
 
-    let taunt = check_taunt(bd, mk);
+    let taunt = check_taunt(bd, mk, rng);
     if taunt.is_some() {
         return taunt;
     }
@@ -46,12 +51,8 @@ pub fn target_for_sneak(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey>
     }
 }
 
-pub fn target_for_opportunity(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
-    if let Some(taunt) = check_taunt(bd, mk) {
-        return Some(taunt);
-    }
-    
-    let taunt = check_taunt(bd, mk);
+pub fn target_for_opportunity(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
+    let taunt = check_taunt(bd, mk, rng);
     if taunt.is_some() {
         return taunt;
     }
@@ -62,18 +63,18 @@ pub fn target_for_opportunity(bd: &BattleData, mk: &MonsterKey) -> Option<Monste
     }
 }
 
-pub fn target_for_melee(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
+pub fn target_for_melee(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
     let monster = bd.get(mk).expect("mk is not in battle");
 
     let mk_pos =  bd.get_pos(mk).expect("mk is not alive");
     let in_1st_pos = mk_pos == 0;
 
     if !in_1st_pos && monster.has_ability(Ability::Sneak) {
-        return target_for_sneak(bd, mk);
+        return target_for_sneak(bd, mk, rng);
     }
 
     if !in_1st_pos && monster.has_ability(Ability::Opportunity) {
-        return target_for_opportunity(bd, mk);
+        return target_for_opportunity(bd, mk, rng);
     }
 
     let in_2nd_pos_with_reach = mk_pos == 1 && monster.has_ability(Ability::Reach);
@@ -88,18 +89,18 @@ pub fn target_for_snipe(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey>
     match mk {
         MonsterKey::Home(_) => bd.oppo_alive.first_from_filter(|tk| {
             let target = bd.get(tk).unwrap();
-            target.get_attack_type() != AttackType::Melee &&
+            !target.has_attack_type(AttackType::Melee) &&
             bd.get_pos(tk).unwrap_or(0) != 0
         }),
         MonsterKey::Oppo(_) => bd.home_alive.first_from_filter(|tk| {
             let target = bd.get(tk).unwrap();
-            target.get_attack_type() != AttackType::Melee &&
+            !target.has_attack_type(AttackType::Melee) &&
             bd.get_pos(tk).unwrap_or(0) != 0
         }),
     }
 }
 
-pub fn target_for_ranged(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
+pub fn target_for_ranged(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
     let monster = bd.get(mk).expect("mk is not in battle");
 
     let in_1st_pos = bd.get_pos(mk).expect("mk is not alive") == 0;
@@ -112,7 +113,7 @@ pub fn target_for_ranged(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey>
         return None;
     }
 
-    let taunt = check_taunt(bd, mk);
+    let taunt = check_taunt(bd, mk, rng);
     if taunt.is_some() {
         return taunt;
     }
@@ -127,10 +128,10 @@ pub fn target_for_ranged(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey>
     target_first_pos(bd, mk)
 }
 
-pub fn target_for_magic(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
+pub fn target_for_magic(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
     let in_1st_pos = bd.get_pos(mk).expect("mk is not alive") == 0;
     if !in_1st_pos {
-        let taunt = check_taunt(bd, mk);
+        let taunt = check_taunt(bd, mk, rng);
         if taunt.is_some() {
             return taunt;
         }
@@ -138,11 +139,10 @@ pub fn target_for_magic(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey>
     target_first_pos(bd, mk)
 }
 
-pub fn target_random(bd: &BattleData, mk: &MonsterKey) -> Option<MonsterKey> {
-    let mut rng = thread_rng();
+pub fn target_random(bd: &BattleData, mk: &MonsterKey, rng: &mut dyn Chance) -> Option<MonsterKey> {
     match mk {
-        MonsterKey::Home(_) => Some(*bd.oppo_alive.choose(&mut rng).unwrap()),
-        MonsterKey::Oppo(_) => Some(*bd.home_alive.choose(&mut rng).unwrap())
+        MonsterKey::Home(_) => Some(*bd.oppo_alive.choose(rng).unwrap()),
+        MonsterKey::Oppo(_) => Some(*bd.home_alive.choose(rng).unwrap())
     }
 }
 
@@ -0,0 +1,21 @@
+use std::fmt::Debug;
+
+use crate::cardparse::enums::AttackType;
+
+use super::{chance::Chance, battledata::BattleData, monsterkey::MonsterKey, targeting};
+
+/// Picks who a monster attacks, swap one into a Battle to change the targeting rules, Send so battles can move between threads
+pub trait TargetingPolicy: Debug + Send {
+    /// Enemy mk hits with its attack of attack_type, None if it can't attack
+    fn target(&self, bd: &BattleData, mk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> Option<MonsterKey>;
+}
+
+/// The standard rules from targeting::target_for_type
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultTargeting;
+
+impl TargetingPolicy for DefaultTargeting {
+    fn target(&self, bd: &BattleData, mk: &MonsterKey, attack_type: AttackType, rng: &mut dyn Chance) -> Option<MonsterKey> {
+        targeting::target_for_type(bd, mk, attack_type, rng)
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Instant;
+
+use splinter_key::{battles::{battle::Battle, ruleset::Ruleset}, cardparse::enums::Outcome, gamedata::{deck::read_decks, registry::Registry}};
+use clappers::Clappers;
+
+/// Plays seeded battles between the two decks in a file and reports battles per second
+///
+/// Battle n uses seed n, the same seeds as `splint -u file -g games -s 0`, so a checkout from
+/// before this binary existed gives the baseline: time that command there in a release build and
+/// divide games by seconds. Its win rate and average rounds should match the wins and rounds here
+fn main() {
+    let clappers = Clappers::build()
+        .set_flags(vec![
+            "h|help",
+        ])
+        .set_singles(vec![
+            "b|battle",
+            "g|games",
+            "c|cards",
+            "r|rulesets",
+            "a|aliases",
+        ])
+        .parse();
+
+    if clappers.get_flag("help") {
+        println!("
+            usage: bench [arguments]
+            eg:    bench -g 100000
+
+            Arguments:
+                -h|--help                        Print this help
+                -b|--battle file                 The 2 decks to play, one per line, default assets/decks/example.txt
+                -g|--games games                 # of battles to play, default 20000
+                -c|--cards file                  Card set, default assets/new_cards.csv
+                -a|--aliases file                Card name aliases, default assets/aliases.csv
+                -r|--rulesets rulesets           Comma separated rulesets, eg \"earthquake,super sneak\"
+        ");
+        return;
+    }
+
+    let or_default = |name: &str, default: &str| {
+        let value = clappers.get_single(name);
+        if value.is_empty() { default.to_owned() } else { value }
+    };
+    let mut reg = Registry::load(&or_default("cards", "assets/new_cards.csv")).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Err(e) = reg.load_aliases(&or_default("aliases", "assets/aliases.csv")) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let (home, oppo) = read_decks(&reg, &or_default("battle", "assets/decks/example.txt")).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let games: u64 = clappers.get_single("games").parse().unwrap_or(20_000);
+    let rulesets = Ruleset::parse_list(&clappers.get_single("rulesets")).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let start = Instant::now();
+    let mut wins = 0;
+    let mut rounds = 0;
+    for seed in 0..games {
+        let mut battle = Battle::from_rulesets(&home, &oppo, &rulesets, rand::SeedableRng::seed_from_u64(seed));
+        if battle.game() == Outcome::Win {
+            wins += 1;
+        }
+        rounds += battle.round_num as u64;
+    }
+    let secs = start.elapsed().as_secs_f64();
+
+    println!("{} vs {}", home, oppo);
+    println!("Battles: {}  Home wins: {}  Rounds: {}", games, wins, rounds);
+    println!("Time: {:.3}s  Battles/s: {:.0}  Rounds/s: {:.0}", secs, games as f64 / secs, rounds as f64 / secs);
+}
@@ -1,36 +1,217 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc};
 
+use serde::{Deserialize, Serialize};
+
+use super::carderror::FieldError;
 use super::cardparser::RawCardData;
 use super::enums::*;
 
+/// Damage for each attack type, 0 for the ones a card doesn't have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Damage {
+    pub melee: i32,
+    pub ranged: i32,
+    pub magic: i32,
+}
+
+impl Damage {
+    /// Order a monster makes its attacks in
+    pub const ORDER: [AttackType; 3] = [AttackType::Magic, AttackType::Ranged, AttackType::Melee];
+
+    pub fn get(&self, attack_type: AttackType) -> i32 {
+        match attack_type {
+            AttackType::Melee => self.melee,
+            AttackType::Ranged => self.ranged,
+            AttackType::Magic => self.magic,
+            AttackType::None => 0,
+        }
+    }
+
+    pub fn get_mut(&mut self, attack_type: AttackType) -> Option<&mut i32> {
+        match attack_type {
+            AttackType::Melee => Some(&mut self.melee),
+            AttackType::Ranged => Some(&mut self.ranged),
+            AttackType::Magic => Some(&mut self.magic),
+            AttackType::None => None,
+        }
+    }
+
+    /// Attack types with damage, in attack order
+    pub fn attack_types(&self) -> Vec<AttackType> {
+        self.iter_attack_types().collect()
+    }
+
+    /// Same as attack_types without collecting them
+    pub fn iter_attack_types(self) -> impl Iterator<Item = AttackType> {
+        Self::ORDER.into_iter().filter(move |x| self.get(*x) > 0)
+    }
+}
+
+/// Shared handle to a card's row, cheap to clone and safe to send between threads
+pub type CardRef = Arc<CardData>;
+
 /// raw card data cleaned up eg. with enums instead of strings
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CardData {
     pub name: String,
     pub role: Role,
     pub element: Element,
     pub mana_cost: i32,
-    pub damage: i32,
-    pub attack_type: AttackType,
+    pub damage: Damage,
     pub speed: i32,
     pub health: i32,
     pub armor: i32,
     pub abilities: HashSet<Ability>,
+    pub level: u8,
+    pub rarity: Rarity,
+    /// Files that defined or patched this row, in the order they were applied
+    pub sources: Vec<String>,
+}
+
+impl CardData {
+    /// First attack the card makes, None if it can't attack
+    pub fn attack_type(&self) -> AttackType {
+        self.damage.iter_attack_types().next().unwrap_or(AttackType::None)
+    }
+
+    /// Changes the columns of a patch row, ability columns replace all of the card's abilities and - clears one
+    pub fn patch(&mut self, changes: &[(String, String)]) -> Result<(), FieldError> {
+        let get = |column: &str| changes.iter().find(|(x, _)| x == column).map(|(_, value)| value.as_str());
+        let number = |column: &str, value: &str| value.parse::<i32>().map_err(|e| FieldError {
+            column: column.to_owned(),
+            value: value.to_owned(),
+            message: e.to_string(),
+        });
+
+        // Dmg and AttackType move the first attack together, like they describe it in a card file
+        if get("Dmg").is_some() || get("AttackType").is_some() {
+            let old_type = self.attack_type();
+            let attack_type = match get("AttackType") {
+                Some(value) => AttackType::try_from(value).map_err(|e| FieldError::unknown("AttackType", e))?,
+                None => old_type,
+            };
+            let dmg = match get("Dmg") {
+                Some(value) => number("Dmg", value)?,
+                None => self.damage.get(old_type),
+            };
+            if let Some(value) = self.damage.get_mut(old_type) {
+                *value = 0;
+            }
+            if let Some(value) = self.damage.get_mut(attack_type) {
+                *value = dmg;
+            }
+        }
+
+        let ability_columns: Vec<&str> = ["Ability1", "Ability2"].into_iter()
+            .filter_map(get)
+            .map(|value| if value == "-" { "" } else { value })
+            .collect();
+        if !ability_columns.is_empty() {
+            let column = if get("Ability1").is_some() { "Ability1" } else { "Ability2" };
+            self.abilities = Ability::make_set(&ability_columns).map_err(|e| FieldError::unknown(column, e))?;
+        }
+
+        for (column, value) in changes {
+            match column.as_str() {
+                "Role" => self.role = value.as_str().try_into().map_err(|e| FieldError::unknown(column, e))?,
+                "Element" => self.element = value.as_str().try_into().map_err(|e| FieldError::unknown(column, e))?,
+                "Rarity" => self.rarity = value.as_str().try_into().map_err(|e| FieldError::unknown(column, e))?,
+                "ManaCost" => self.mana_cost = number(column, value)?,
+                "Speed" => self.speed = number(column, value)?,
+                "Health" => self.health = number(column, value)?,
+                "Armor" => self.armor = number(column, value)?,
+                "Melee" => self.damage.melee = number(column, value)?,
+                "Ranged" => self.damage.ranged = number(column, value)?,
+                "Magic" => self.damage.magic = number(column, value)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&CardData> for RawCardData {
+    type Error = FieldError;
+
+    /// The row for a csv file, which has room for two abilities
+    fn try_from(card: &CardData) -> Result<Self, Self::Error> {
+        let mut abilities: Vec<String> = card.abilities.iter().map(|x| x.to_string()).collect();
+        abilities.sort();
+        if abilities.len() > 2 {
+            return Err(FieldError {
+                column: "Ability1".to_owned(),
+                value: abilities.join(", "),
+                message: format!("{} has more abilities than a csv row holds", card.name),
+            });
+        }
+        let mut abilities = abilities.into_iter();
+
+        // Dmg and AttackType hold a single attack, cards with more also fill the per type columns
+        let attack_type = card.attack_type();
+        let multi = card.damage.attack_types().len() > 1;
+        let per_type = |attack_type: AttackType| multi.then(|| card.damage.get(attack_type));
+        Ok(Self {
+            Card: card.name.to_owned(),
+            Role: card.role.to_string(),
+            Element: card.element.to_string(),
+            ManaCost: card.mana_cost,
+            Dmg: card.damage.get(attack_type),
+            AttackType: attack_type.to_string(),
+            Speed: card.speed,
+            Health: card.health,
+            Armor: card.armor,
+            Ability1: abilities.next().unwrap_or_default(),
+            Ability2: abilities.next().unwrap_or_default(),
+            Level: Some(card.level),
+            Rarity: card.rarity.to_string(),
+            Melee: per_type(AttackType::Melee),
+            Ranged: per_type(AttackType::Ranged),
+            Magic: per_type(AttackType::Magic),
+        })
+    }
 }
 
-impl From<RawCardData> for CardData {
-    fn from(raw_card_data: RawCardData) -> Self {
-        Self {
+impl TryFrom<RawCardData> for CardData {
+    type Error = FieldError;
+
+    fn try_from(raw_card_data: RawCardData) -> Result<Self, Self::Error> {
+        // Dmg and AttackType give one attack, the Melee, Ranged and Magic columns override it per type
+        let mut damage = Damage::default();
+        let attack_type = AttackType::try_from(raw_card_data.AttackType.as_str())
+            .map_err(|e| FieldError::unknown("AttackType", e))?;
+        if let Some(value) = damage.get_mut(attack_type) {
+            *value = raw_card_data.Dmg;
+        }
+        for (attack_type, column) in [
+            (AttackType::Melee, raw_card_data.Melee),
+            (AttackType::Ranged, raw_card_data.Ranged),
+            (AttackType::Magic, raw_card_data.Magic),
+        ] {
+            if let (Some(value), Some(dmg)) = (damage.get_mut(attack_type), column) {
+                *value = dmg;
+            }
+        }
+
+        let abilities = Ability::make_set(&[&raw_card_data.Ability1])
+            .map_err(|e| FieldError::unknown("Ability1", e))?
+            .into_iter()
+            .chain(Ability::make_set(&[&raw_card_data.Ability2]).map_err(|e| FieldError::unknown("Ability2", e))?)
+            .collect();
+
+        Ok(Self {
+            role: raw_card_data.Role.as_str().try_into().map_err(|e| FieldError::unknown("Role", e))?,
+            element: raw_card_data.Element.as_str().try_into().map_err(|e| FieldError::unknown("Element", e))?,
+            rarity: raw_card_data.Rarity.as_str().try_into().map_err(|e| FieldError::unknown("Rarity", e))?,
             name: raw_card_data.Card,
-            role: raw_card_data.Role.as_str().into(),
-            element: raw_card_data.Element.as_str().into(),
             mana_cost: raw_card_data.ManaCost,
-            damage: raw_card_data.Dmg,
-            attack_type: raw_card_data.AttackType.as_str().into(),
+            damage,
             speed: raw_card_data.Speed,
             health: raw_card_data.Health,
             armor: raw_card_data.Armor,
-            abilities: Ability::make_set(&[&raw_card_data.Ability1, &raw_card_data.Ability2]),
-        }
+            abilities,
+            level: raw_card_data.Level.unwrap_or(1),
+            sources: Vec::new(),
+        })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,141 @@
+use std::fmt::Display;
+
+/// A string that isn't one of an enum's values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownValue {
+    pub kind: &'static str,
+    pub value: String,
+}
+
+impl UnknownValue {
+    pub fn new(kind: &'static str, value: &str) -> Self {
+        Self {
+            kind,
+            value: value.to_owned(),
+        }
+    }
+}
+
+impl Display for UnknownValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is not a known {}", self.value, self.kind)
+    }
+}
+
+/// A deck names a card the registry doesn't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownCard {
+    pub name: String,
+    /// Closest names by edit distance, best first
+    pub suggestions: Vec<String>,
+    /// The level asked for and the card's highest, when the card exists but not at that level
+    pub level: Option<(u8, u8)>,
+}
+
+impl Display for UnknownCard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((level, max)) = self.level {
+            return write!(f, "card '{}' has no level {}, its levels are 1 to {}", self.name, level, max);
+        }
+        write!(f, "card '{}' not found in registry", self.name)?;
+        if !self.suggestions.is_empty() {
+            write!(f, ", did you mean {}?", self.suggestions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownCard {}
+
+/// A bad value in one column of a card row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub column: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn unknown(column: &str, err: UnknownValue) -> Self {
+        Self {
+            column: column.to_owned(),
+            value: err.value.to_owned(),
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Why a card file or one of its rows couldn't be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardError {
+    Io { file: String, message: String },
+    /// The row itself is broken, like a wrong number of fields
+    Row { file: String, line: u64, message: String },
+    Field { file: String, line: u64, column: String, value: String, message: String },
+}
+
+impl CardError {
+    pub fn field(file: &str, line: u64, err: FieldError) -> Self {
+        Self::Field {
+            file: file.to_owned(),
+            line,
+            column: err.column,
+            value: err.value,
+            message: err.message,
+        }
+    }
+}
+
+impl Display for CardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardError::Io { file, message } => write!(f, "{}: {}", file, message),
+            CardError::Row { file, line, message } => write!(f, "{}:{}: {}", file, line, message),
+            CardError::Field { file, line, column, value, message } => {
+                write!(f, "{}:{}: column {} has bad value '{}': {}", file, line, column, value, message)
+            },
+        }
+    }
+}
+
+impl std::error::Error for CardError {}
+
+/// Something in a card file that loads fine but is probably a mistake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardWarning {
+    pub file: String,
+    pub line: u64,
+    pub card: String,
+    pub message: String,
+}
+
+impl Display for CardWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: warning: {} {}", self.file, self.line, self.card, self.message)
+    }
+}
+
+/// Every problem found in a card file, from Registry::validate
+#[derive(Debug, Default)]
+pub struct CardReport {
+    pub errors: Vec<CardError>,
+    pub warnings: Vec<CardWarning>,
+}
+
+impl CardReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl Display for CardReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "{}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "{}", warning)?;
+        }
+        write!(f, "{} errors, {} warnings", self.errors.len(), self.warnings.len())
+    }
+}
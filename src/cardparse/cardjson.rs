@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, fs};
+
+use serde::{Deserialize, Serialize};
+
+use super::{carddata::{CardData, Damage}, carderror::CardError, enums::{Ability, Element, Rarity, Role}};
+
+/// A card in a json card file, with every level it has stats for
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonCard {
+    pub name: String,
+    pub role: Role,
+    pub element: Element,
+    #[serde(default)]
+    pub rarity: Rarity,
+    pub levels: Vec<JsonLevel>,
+}
+
+/// Stats for one level, unlike a csv row it takes any number of abilities and attacks
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonLevel {
+    #[serde(default = "first_level")]
+    pub level: u8,
+    pub mana_cost: i32,
+    #[serde(default)]
+    pub damage: Damage,
+    pub speed: i32,
+    pub health: i32,
+    #[serde(default)]
+    pub armor: i32,
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+}
+
+fn first_level() -> u8 {
+    1
+}
+
+impl JsonCard {
+    /// One row per level, each remembering path as its source
+    pub fn into_cards(self, path: &str) -> impl Iterator<Item = CardData> + '_ {
+        let Self { name, role, element, rarity, levels } = self;
+        levels.into_iter().map(move |stats| CardData {
+            name: name.to_owned(),
+            role,
+            element,
+            mana_cost: stats.mana_cost,
+            damage: stats.damage,
+            speed: stats.speed,
+            health: stats.health,
+            armor: stats.armor,
+            abilities: stats.abilities.into_iter().collect(),
+            level: stats.level,
+            rarity,
+            sources: vec![path.to_owned()],
+        })
+    }
+}
+
+/// Reads a json card file, the error points at the line and column serde stopped on
+pub fn read_cards(path: &str) -> Result<Vec<CardData>, CardError> {
+    let content = fs::read_to_string(path).map_err(|e| CardError::Io { file: path.to_owned(), message: e.to_string() })?;
+    let cards: Vec<JsonCard> = serde_json::from_str(&content).map_err(|e| CardError::Row {
+        file: path.to_owned(),
+        line: e.line() as u64,
+        message: e.to_string(),
+    })?;
+    Ok(cards.into_iter().flat_map(|card| card.into_cards(path)).collect())
+}
+
+/// Groups rows into cards sorted by name and level, role, element and rarity come from the lowest level
+pub fn to_json_cards<'a>(cards: impl IntoIterator<Item = &'a CardData>) -> Vec<JsonCard> {
+    let mut by_name: BTreeMap<&str, BTreeMap<u8, &CardData>> = BTreeMap::new();
+    for card in cards {
+        by_name.entry(&card.name).or_default().insert(card.level, card);
+    }
+    by_name.into_iter().map(|(name, levels)| {
+        let first = levels.values().next().expect("every card has a row");
+        JsonCard {
+            name: name.to_owned(),
+            role: first.role,
+            element: first.element,
+            rarity: first.rarity,
+            levels: levels.values().map(|card| {
+                let mut abilities: Vec<Ability> = card.abilities.iter().copied().collect();
+                abilities.sort_by_key(|x| x.to_string());
+                JsonLevel {
+                    level: card.level,
+                    mana_cost: card.mana_cost,
+                    damage: card.damage,
+                    speed: card.speed,
+                    health: card.health,
+                    armor: card.armor,
+                    abilities,
+                }
+            }).collect(),
+        }
+    }).collect()
+}
+
+/// Writes cards as pretty printed json
+pub fn write_cards<'a>(path: &str, cards: impl IntoIterator<Item = &'a CardData>) -> Result<(), CardError> {
+    let io_error = |message: String| CardError::Io { file: path.to_owned(), message };
+    let json = serde_json::to_string_pretty(&to_json_cards(cards)).map_err(|e| io_error(e.to_string()))?;
+    fs::write(path, json + "\n").map_err(|e| io_error(e.to_string()))
+}
@@ -1,13 +1,12 @@
-use csv::{Reader, DeserializeRecordsIter};
-use serde::Deserialize;
-use std::{fs, collections::HashMap};
+use csv::{Reader, StringRecord, Writer};
+use serde::{Deserialize, Serialize};
+use std::fs;
 
-use crate::gamedata::registry::Registry;
+use super::{carddata::CardData, carderror::CardError};
 
-use super::carddata::CardData;
-
-/// Raw parsed data for each card
-#[derive(Debug, Deserialize, PartialEq, Eq, Hash)]
+/// Raw parsed data for each card, fields are named after the csv headers
+#[allow(non_snake_case)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub struct RawCardData {
     pub Card: String,
     pub Role: String,
@@ -20,18 +19,150 @@ pub struct RawCardData {
     pub Armor: i32,
     pub Ability1: String,
     pub Ability2: String,
+    /// Optional column, rows without it are level 1
+    #[serde(default)]
+    pub Level: Option<u8>,
+    /// Optional column, rows without it are common
+    #[serde(default)]
+    pub Rarity: String,
+    /// Optional per type damage columns for cards with more than one attack
+    #[serde(default)]
+    pub Melee: Option<i32>,
+    #[serde(default)]
+    pub Ranged: Option<i32>,
+    #[serde(default)]
+    pub Magic: Option<i32>,
+}
+
+/// A card and the line of the file it was read from
+pub type CardRow = (u64, CardData);
+
+/// Columns a patch file can change, Card and Level pick the rows to change
+pub const PATCH_COLUMNS: [&str; 14] = [
+    "Role", "Element", "ManaCost", "Dmg", "AttackType", "Speed", "Health", "Armor",
+    "Ability1", "Ability2", "Rarity", "Melee", "Ranged", "Magic",
+];
+
+/// One row of a patch file, only the cells it fills in change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchRow {
+    pub line: u64,
+    pub card: String,
+    /// Every level of the card if None
+    pub level: Option<u8>,
+    /// Column and value of each filled in cell
+    pub changes: Vec<(String, String)>,
+}
+
+/// Reads every Summoner and Monster row, one per card level, stopping at the first problem
+pub fn get_cards(path: &str) -> Result<Vec<CardData>, CardError> {
+    parse_cards(path)?
+        .into_iter()
+        .map(|row| row.map(|(_, card)| card))
+        .collect()
 }
 
-/// Registers Summoners and Monsters
-pub fn get_map(path: &str) -> Result<HashMap<String, CardData>, csv::Error> {
-    let mut map = HashMap::new();
-    let content = fs::read_to_string(path)?;
+/// Reads every row of path, a file that can't be read is an error and so is each bad row
+pub fn parse_cards(path: &str) -> Result<Vec<Result<CardRow, CardError>>, CardError> {
+    let content = fs::read_to_string(path).map_err(|e| CardError::Io { file: path.to_owned(), message: e.to_string() })?;
     let mut reader = Reader::from_reader(content.as_bytes());
-    for data in reader.deserialize() {
-        let raw_card: RawCardData = data?;
-        let card: CardData = raw_card.into();
-        map.insert(card.name.to_owned(), card);
+    let headers = reader.headers().map_err(|e| row_error(path, 1, &e))?.clone();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|pos| pos.line()).unwrap_or_default();
+                rows.push(Err(row_error(path, line, &e)));
+                continue;
+            },
+        };
+        let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+        let row = record.deserialize::<RawCardData>(Some(&headers))
+            .map_err(|e| deserialize_error(path, line, &headers, &record, &e))
+            .and_then(|raw_card| CardData::try_from(raw_card).map_err(|e| CardError::field(path, line, e)))
+            .map(|mut card| {
+                card.sources.push(path.to_owned());
+                (line, card)
+            });
+        rows.push(row);
     }
 
-    Ok(map)
+    Ok(rows)
+}
+
+/// Writes cards to a csv file with every optional column, fails on a card the format can't hold
+pub fn write_cards<'a>(path: &str, cards: impl IntoIterator<Item = &'a CardData>) -> Result<(), CardError> {
+    // header is line 1, rows are checked before the file is touched
+    let rows = (2..).zip(cards)
+        .map(|(line, card)| RawCardData::try_from(card).map_err(|e| CardError::field(path, line, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let io_error = |message: String| CardError::Io { file: path.to_owned(), message };
+    let mut writer = Writer::from_path(path).map_err(|e| io_error(e.to_string()))?;
+    for row in rows {
+        writer.serialize(row).map_err(|e| io_error(e.to_string()))?;
+    }
+    writer.flush().map_err(|e| io_error(e.to_string()))
+}
+
+/// Reads a balance patch, a csv with a Card column, an optional Level column and any of PATCH_COLUMNS
+pub fn parse_patch(path: &str) -> Result<Vec<PatchRow>, CardError> {
+    let content = fs::read_to_string(path).map_err(|e| CardError::Io { file: path.to_owned(), message: e.to_string() })?;
+    let mut reader = Reader::from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| row_error(path, 1, &e))?.clone();
+    if !headers.iter().any(|x| x == "Card") {
+        return Err(CardError::Row { file: path.to_owned(), line: 1, message: "patch has no Card column".to_owned() });
+    }
+    if let Some(column) = headers.iter().find(|x| *x != "Card" && *x != "Level" && !PATCH_COLUMNS.contains(x)) {
+        return Err(CardError::Row { file: path.to_owned(), line: 1, message: format!("unknown column {}", column) });
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| row_error(path, e.position().map(|pos| pos.line()).unwrap_or_default(), &e))?;
+        let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+        let mut row = PatchRow { line, card: String::new(), level: None, changes: Vec::new() };
+        for (column, value) in headers.iter().zip(record.iter()) {
+            let value = value.trim();
+            match column {
+                "Card" => row.card = value.to_owned(),
+                "Level" if !value.is_empty() => {
+                    row.level = Some(value.parse().map_err(|e: std::num::ParseIntError| CardError::Field {
+                        file: path.to_owned(),
+                        line,
+                        column: column.to_owned(),
+                        value: value.to_owned(),
+                        message: e.to_string(),
+                    })?);
+                },
+                _ if !value.is_empty() => row.changes.push((column.to_owned(), value.to_owned())),
+                _ => (),
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn row_error(path: &str, line: u64, err: &csv::Error) -> CardError {
+    CardError::Row { file: path.to_owned(), line, message: err.to_string() }
+}
+
+/// Points at the column serde failed on when it knows which one
+fn deserialize_error(path: &str, line: u64, headers: &StringRecord, record: &StringRecord, err: &csv::Error) -> CardError {
+    if let csv::ErrorKind::Deserialize { err: de_err, .. } = err.kind() {
+        if let Some(i) = de_err.field() {
+            let i = i as usize;
+            return CardError::Field {
+                file: path.to_owned(),
+                line,
+                column: headers.get(i).unwrap_or_default().to_owned(),
+                value: record.get(i).unwrap_or_default().to_owned(),
+                message: de_err.kind().to_string(),
+            };
+        }
+        return CardError::Row { file: path.to_owned(), line, message: de_err.kind().to_string() };
+    }
+    row_error(path, line, err)
 }
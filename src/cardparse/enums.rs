@@ -1,4 +1,28 @@
-use std::{panic, collections::HashSet, fmt::Display};
+use std::{collections::HashSet, fmt::Display};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::carderror::UnknownValue;
+
+/// Serializes enums as the names the card files use, through Display and TryFrom<&str>
+macro_rules! serde_by_name {
+    ($($enum:ty),*) => {$(
+        impl Serialize for $enum {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $enum {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let string = String::deserialize(deserializer)?;
+                Self::try_from(string.as_str()).map_err(serde::de::Error::custom)
+            }
+        }
+    )*};
+}
+
+serde_by_name!(Role, Rarity, Element, AttackType, Ability);
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Role {
@@ -6,13 +30,59 @@ pub enum Role {
     Monster,
 }
 
-impl From<&str> for Role {
-    fn from(string: &str) -> Self {
-        match string {
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Summoner => "summoner",
+            Self::Monster => "monster",
+        })
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = UnknownValue;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Ok(match string {
             "summoner" => Self::Summoner,
             "monster" => Self::Monster,
-            &_ => {panic!("{} could not be cast into Role", string);},
-        }
+            &_ => return Err(UnknownValue::new("Role", string)),
+        })
+    }
+}
+
+/// Ordered from most to least common
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl Display for Rarity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Common => "common",
+            Self::Rare => "rare",
+            Self::Epic => "epic",
+            Self::Legendary => "legendary",
+        })
+    }
+}
+
+impl TryFrom<&str> for Rarity {
+    type Error = UnknownValue;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Ok(match string {
+            "" | "common" => Self::Common,
+            "rare" => Self::Rare,
+            "epic" => Self::Epic,
+            "legendary" => Self::Legendary,
+            &_ => return Err(UnknownValue::new("Rarity", string)),
+        })
     }
 }
 
@@ -27,9 +97,25 @@ pub enum Element {
     Dragon,
 }
 
-impl From<&str> for Element {
-    fn from(string: &str) -> Self {
-        match string {
+impl Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fire => "fire",
+            Self::Water => "water",
+            Self::Neutral => "neutral",
+            Self::Earth => "earth",
+            Self::Life => "life",
+            Self::Death => "death",
+            Self::Dragon => "dragon",
+        })
+    }
+}
+
+impl TryFrom<&str> for Element {
+    type Error = UnknownValue;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Ok(match string {
             "fire" => Self::Fire,
             "water" => Self::Water,
             "neutral" => Self::Neutral,
@@ -37,8 +123,8 @@ impl From<&str> for Element {
             "life" => Self::Life,
             "death" => Self::Death,
             "dragon" => Self::Dragon,
-            &_ => {panic!("{} could not be cast into Element", string);},
-        }
+            &_ => return Err(UnknownValue::new("Element", string)),
+        })
     }
 }
 
@@ -50,19 +136,32 @@ pub enum AttackType {
     Magic,
 }
 
-impl From<&str> for AttackType {
-    fn from(string: &str) -> Self {
-        match string {
+impl Display for AttackType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Melee => "melee",
+            Self::Ranged => "ranged",
+            Self::Magic => "magic",
+        })
+    }
+}
+
+impl TryFrom<&str> for AttackType {
+    type Error = UnknownValue;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Ok(match string {
             "none" => Self::None,
             "melee" => Self::Melee,
             "ranged" => Self::Ranged,
             "magic" => Self::Magic,
-            &_ => {panic!("{} could not be cast into AttackType", string);},
-        }
+            &_ => return Err(UnknownValue::new("AttackType", string)),
+        })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub enum Ability {
     Melee(i8),
     Ranged(i8),
@@ -103,9 +202,61 @@ pub enum Ability {
     Dispel,
 }
 
-impl From<&str> for Ability {
-    fn from(string: &str) -> Self {
-        match string {
+impl Display for Ability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // stat abilities are the stat with a + or - for each point, like armor--
+        let (stat, i) = match self {
+            Self::Melee(i) => ("melee", i),
+            Self::Ranged(i) => ("ranged", i),
+            Self::Magic(i) => ("magic", i),
+            Self::Speed(i) => ("speed", i),
+            Self::Armor(i) => ("armor", i),
+            Self::Health(i) => ("health", i),
+            _ => return f.write_str(match self {
+                Self::MagicReflect => "magic reflect",
+                Self::Shield => "shield",
+                Self::Flying => "flying",
+                Self::Heal => "heal",
+                Self::Void => "void",
+                Self::Dodge => "dodge",
+                Self::Reach => "reach",
+                Self::Stun => "stun",
+                Self::Sneak => "sneak",
+                Self::Blast => "blast",
+                Self::Weaken => "weaken",
+                Self::Inspire => "inspire",
+                Self::Snipe => "snipe",
+                Self::Opportunity => "opportunity",
+                Self::Protect => "protect",
+                Self::TankHeal => "tank heal",
+                Self::Taunt => "taunt",
+                Self::Retaliate => "retaliate",
+                Self::Cleanse => "cleanse",
+                Self::CloseRange => "close range",
+                Self::Trample => "trample",
+                Self::Thorns => "thorns",
+                Self::Slow => "slow",
+                Self::Scattershot => "scattershot",
+                Self::DoubleStrike => "double strike",
+                Self::Scavenger => "scavenger",
+                Self::LifeLeech => "life leech",
+                Self::TrueStrike => "true strike",
+                Self::Repair => "repair",
+                Self::Demoralize => "demoralize",
+                Self::Dispel => "dispel",
+                _ => unreachable!("stat abilities are matched above"),
+            }),
+        };
+        let sign = if *i < 0 { "-" } else { "+" };
+        write!(f, "{}{}", stat, sign.repeat(i.unsigned_abs() as usize))
+    }
+}
+
+impl TryFrom<&str> for Ability {
+    type Error = UnknownValue;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Ok(match string {
             "melee" => Self::Melee(0),
             "melee+" => Self::Melee(1),
             "melee-" => Self::Melee(-1),
@@ -154,21 +305,21 @@ impl From<&str> for Ability {
             "repair" => Self::Repair,
             "demoralize" => Self::Demoralize,
             "dispel" => Self::Dispel,
-            &_  => {panic!("{} could not be cast into Ability", string);},
-        }
+            &_ => return Err(UnknownValue::new("Ability", string)),
+        })
     }
 }
 
 impl Ability {
-    pub fn make_set(strings: &[&str]) -> HashSet<Ability> {
+    pub fn make_set(strings: &[&str]) -> Result<HashSet<Ability>, UnknownValue> {
         let mut set = HashSet::new();
         for &string in strings {
             if string.is_empty() {
                 continue;
             }
-            set.insert(string.into());
+            set.insert(string.try_into()?);
         }
-        set
+        Ok(set)
     }
 
     // pub fn extent_of(i: i8) -> i32 {
@@ -180,30 +331,58 @@ impl Ability {
     // }
 
     pub fn is_buff(&self) -> bool {
-        match self {
-            &Ability::Health(i) => i >= 0,
-            &Ability::Armor(i) => i >= 0,
-            &Ability::Speed(i) => i >= 0,
-            &Ability::Melee(i) => i >= 0,
-            &Ability::Ranged(i) => i >= 0,
-            &Ability::Magic(i) => i >= 0,
+        match *self {
+            Ability::Health(i) => i >= 0,
+            Ability::Armor(i) => i >= 0,
+            Ability::Speed(i) => i >= 0,
+            Ability::Melee(i) => i >= 0,
+            Ability::Ranged(i) => i >= 0,
+            Ability::Magic(i) => i >= 0,
             _ => false,
         }
     }
 
-    pub fn is_debuff(&self) -> bool {
+    /// Attack type a damage buff or debuff applies to
+    pub fn damage_type(&self) -> Option<AttackType> {
         match self {
-            &Ability::Health(i) => i < 0,
-            &Ability::Armor(i) => i < 0,
-            &Ability::Speed(i) => i < 0,
-            &Ability::Melee(i) => i < 0,
-            &Ability::Ranged(i) => i < 0,
-            &Ability::Magic(i) => i < 0,
+            Ability::Melee(_) => Some(AttackType::Melee),
+            Ability::Ranged(_) => Some(AttackType::Ranged),
+            Ability::Magic(_) => Some(AttackType::Magic),
+            _ => None,
+        }
+    }
+
+    /// Stat change a monster's aura gives and which team it lands on
+    pub fn aura_effect(&self) -> Option<(Ability, AuraTarget)> {
+        match *self {
+            Ability::Inspire => Some((Ability::Melee(1), AuraTarget::Allies)),
+            Ability::Protect => Some((Ability::Armor(2), AuraTarget::Allies)),
+            Ability::Weaken => Some((Ability::Health(-1), AuraTarget::Enemies)),
+            Ability::Slow => Some((Ability::Speed(-1), AuraTarget::Enemies)),
+            Ability::Demoralize => Some((Ability::Melee(-1), AuraTarget::Enemies)),
+            _ => None,
+        }
+    }
+
+    pub fn is_debuff(&self) -> bool {
+        match *self {
+            Ability::Health(i) => i < 0,
+            Ability::Armor(i) => i < 0,
+            Ability::Speed(i) => i < 0,
+            Ability::Melee(i) => i < 0,
+            Ability::Ranged(i) => i < 0,
+            Ability::Magic(i) => i < 0,
             _ => false,
         }
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum AuraTarget {
+    Allies,
+    Enemies,
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Outcome {
     Win,
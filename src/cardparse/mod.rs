@@ -1,3 +1,5 @@
 pub mod carddata;
+pub mod carderror;
+pub mod cardjson;
 pub mod cardparser;
 pub mod enums;
\ No newline at end of file
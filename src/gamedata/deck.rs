@@ -0,0 +1,221 @@
+use std::fmt::Display;
+
+use crate::cardparse::{carddata::CardRef, carderror::UnknownCard, enums::{Element, Role}};
+
+use super::registry::Registry;
+
+/// Most monsters a deck can hold
+pub const MAX_MONSTERS: usize = 6;
+
+/// Checks a deck has to pass on top of the ones every deck does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeckRules {
+    /// Most mana the summoner and monsters can cost together
+    pub mana_cap: Option<i32>,
+    /// Monsters must be an element the summoner allows
+    pub check_elements: bool,
+}
+
+impl Default for DeckRules {
+    fn default() -> Self {
+        Self {
+            mana_cap: None,
+            check_elements: true,
+        }
+    }
+}
+
+impl DeckRules {
+    pub fn mana(cap: i32) -> Self {
+        Self {
+            mana_cap: Some(cap),
+            ..Self::default()
+        }
+    }
+
+    /// Lets any element play with any summoner, for setting up a scenario
+    pub fn sandbox() -> Self {
+        Self {
+            check_elements: false,
+            ..Self::default()
+        }
+    }
+}
+
+/// Why a list of cards isn't a deck
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeckError {
+    Empty,
+    UnknownCard(UnknownCard),
+    /// The 1st card has to be a summoner
+    NoSummoner { card: String },
+    /// A summoner after the 1st card
+    ExtraSummoner { card: String },
+    TooManyMonsters { count: usize },
+    Duplicate { card: String },
+    OverManaCap { mana: i32, cap: i32 },
+    ElementNotAllowed { card: String, element: Element, summoner: String },
+}
+
+impl From<UnknownCard> for DeckError {
+    fn from(err: UnknownCard) -> Self {
+        Self::UnknownCard(err)
+    }
+}
+
+impl Display for DeckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeckError::Empty => write!(f, "deck has no cards"),
+            DeckError::UnknownCard(err) => write!(f, "{}", err),
+            DeckError::NoSummoner { card } => write!(f, "1st card {} is not a summoner", card),
+            DeckError::ExtraSummoner { card } => write!(f, "{} is a summoner but only the 1st card can be", card),
+            DeckError::TooManyMonsters { count } => write!(f, "deck has {} monsters, the most is {}", count, MAX_MONSTERS),
+            DeckError::Duplicate { card } => write!(f, "{} is in the deck more than once", card),
+            DeckError::OverManaCap { mana, cap } => write!(f, "deck costs {} mana, the cap is {}", mana, cap),
+            DeckError::ElementNotAllowed { card, element, summoner } => {
+                write!(f, "{} is {} which {} doesn't allow", card, element, summoner)
+            },
+        }
+    }
+}
+
+impl std::error::Error for DeckError {}
+
+/// A summoner and up to 6 monsters, front to back, that passed the deck rules
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deck {
+    pub summoner: CardRef,
+    pub monsters: Vec<CardRef>,
+}
+
+impl Deck {
+    /// Deck entries like "Kobold Bruiser:3", summoner 1st, checked with the default rules
+    pub fn new(reg: &Registry, cards: &[&str]) -> Result<Self, DeckError> {
+        Self::with_rules(reg, cards, DeckRules::default())
+    }
+
+    pub fn with_rules(reg: &Registry, cards: &[&str], rules: DeckRules) -> Result<Self, DeckError> {
+        let (summoner, monsters) = cards.split_first().ok_or(DeckError::Empty)?;
+        let summoner = reg.get_card(summoner)?.clone();
+        let monsters = monsters.iter()
+            .map(|entry| reg.get_card(entry).cloned())
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_cards(summoner, monsters, rules)
+    }
+
+    /// Builds a deck from rows already in a registry
+    pub fn from_cards(summoner: CardRef, monsters: Vec<CardRef>, rules: DeckRules) -> Result<Self, DeckError> {
+        if summoner.role != Role::Summoner {
+            return Err(DeckError::NoSummoner { card: summoner.name.to_owned() });
+        }
+        if monsters.len() > MAX_MONSTERS {
+            return Err(DeckError::TooManyMonsters { count: monsters.len() });
+        }
+        for (i, monster) in monsters.iter().enumerate() {
+            if monster.role == Role::Summoner {
+                return Err(DeckError::ExtraSummoner { card: monster.name.to_owned() });
+            }
+            if monsters[..i].iter().any(|x| x.name == monster.name) {
+                return Err(DeckError::Duplicate { card: monster.name.to_owned() });
+            }
+        }
+        let deck = Self { summoner, monsters };
+        if let Some(cap) = rules.mana_cap {
+            let mana = deck.mana_cost();
+            if mana > cap {
+                return Err(DeckError::OverManaCap { mana, cap });
+            }
+        }
+        if rules.check_elements {
+            deck.check_elements()?;
+        }
+        Ok(deck)
+    }
+
+    pub fn mana_cost(&self) -> i32 {
+        self.summoner.mana_cost + self.monsters.iter().map(|x| x.mana_cost).sum::<i32>()
+    }
+
+    /// Neutral monsters go with anyone, a dragon summoner allows dragons and one other element
+    fn check_elements(&self) -> Result<(), DeckError> {
+        let summ = &self.summoner;
+        let mut dragon_partner = None;
+        for monster in &self.monsters {
+            let allowed = match (summ.element, monster.element) {
+                (_, Element::Neutral) | (Element::Neutral, _) => true,
+                (summ_elem, elem) if summ_elem == elem => true,
+                (Element::Dragon, elem) => *dragon_partner.get_or_insert(elem) == elem,
+                _ => false,
+            };
+            if !allowed {
+                return Err(DeckError::ElementNotAllowed {
+                    card: monster.name.to_owned(),
+                    element: monster.element,
+                    summoner: summ.name.to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Deck entries, with the level for cards above level 1
+    pub fn entries(&self) -> Vec<String> {
+        std::iter::once(&self.summoner)
+            .chain(self.monsters.iter())
+            .map(|card| match card.level {
+                1 => card.name.to_owned(),
+                level => format!("{}:{}", card.name, level),
+            })
+            .collect()
+    }
+}
+
+impl Display for Deck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.entries())
+    }
+}
+
+/// Reads the two decks in path, one per line with comma separated cards, home first
+pub fn read_decks(reg: &Registry, path: &str) -> Result<(Deck, Deck), String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let decks = content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let cards: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+            Deck::new(reg, &cards).map_err(|e| format!("{}:{}: {}", path, i + 1, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [home, oppo] = <[Deck; 2]>::try_from(decks)
+        .map_err(|decks| format!("{}: expected 2 decks, found {}", path, decks.len()))?;
+    Ok((home, oppo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deck_rules() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let deck = Deck::new(&reg, &["Tarsa", "living lava", "Magma Troll"]).unwrap();
+        assert_eq!(deck.entries(), vec!["Tarsa", "Living Lava", "Magma Troll"]);
+        assert_eq!(deck.mana_cost(), reg.map["Tarsa"].mana_cost + reg.map["Living Lava"].mana_cost + reg.map["Magma Troll"].mana_cost);
+
+        let err = |cards: &[&str], rules: DeckRules| Deck::with_rules(&reg, cards, rules).unwrap_err();
+        assert_eq!(err(&[], DeckRules::default()), DeckError::Empty);
+        assert!(matches!(err(&["Tarsa", "Magma Trol"], DeckRules::default()), DeckError::UnknownCard(_)));
+        assert_eq!(err(&["Magma Troll", "Tarsa"], DeckRules::default()), DeckError::NoSummoner { card: "Magma Troll".to_owned() });
+        assert_eq!(err(&["Tarsa", "Pyre"], DeckRules::default()), DeckError::ExtraSummoner { card: "Pyre".to_owned() });
+        assert_eq!(err(&["Tarsa", "Magma Troll", "Magma Troll"], DeckRules::default()), DeckError::Duplicate { card: "Magma Troll".to_owned() });
+        let seven = ["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Lava Spider", "Kobold Bruiser", "Goblin Fireballer"];
+        assert_eq!(err(&seven, DeckRules::default()), DeckError::TooManyMonsters { count: 7 });
+        assert!(matches!(err(&["Tarsa", "Living Lava", "Magma Troll"], DeckRules::mana(5)), DeckError::OverManaCap { cap: 5, .. }));
+
+        let water = err(&["Tarsa", "Sniping Narwhal"], DeckRules::default());
+        assert_eq!(water.to_string(), "Sniping Narwhal is water which Tarsa doesn't allow");
+        assert!(Deck::with_rules(&reg, &["Tarsa", "Sniping Narwhal"], DeckRules::sandbox()).is_ok());
+    }
+}
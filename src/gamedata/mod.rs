@@ -1,3 +1,5 @@
 pub mod monster;
+pub mod statuseffect;
 pub mod registry;
+pub mod deck;
 pub mod summoner;
\ No newline at end of file
@@ -1,36 +1,73 @@
-use std::fmt::Display;
-use std::rc::Rc;
-use std::cmp::Ordering;
+use std::{fmt::Display, hash::{Hash, Hasher}, sync::Arc};
 
-use crate::cardparse::carddata::CardData;
-use crate::cardparse::enums::{AttackType, Ability};
+use crate::cardparse::carddata::{CardData, CardRef, Damage};
+use crate::cardparse::enums::{AttackType, Ability, Rarity};
 
-#[derive(Debug)]
-pub struct Monster<'a> {
-    type_object: &'a CardData,
+use super::statuseffect::{StatusEffect, StackRule, Tick};
+
+#[derive(Debug, Clone)]
+pub struct Monster {
+    type_object: CardRef,
     key: u8,
     max_health: i32,
     health: i32,
+    max_armor: i32,
     armor: i32,
-    damage: i32,
+    damage: Damage,
     speed: i32,
     buffs_provided: Vec<Ability>,
+    granted_abilities: Vec<Ability>,
+    abilities_removed: bool,
+    effects: Vec<StatusEffect>,
+}
+
+/// Same card handle and the same stats and effects, so identical battle states can be merged
+impl PartialEq for Monster {
+    fn eq(&self, other: &Self) -> bool {
+        self.state() == other.state()
+    }
 }
 
-impl<'a> Monster<'a> {
-    pub fn new(type_object: &'a CardData, key: u8) -> Self {
+impl Eq for Monster {}
+
+impl Hash for Monster {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.state().hash(state);
+    }
+}
+
+impl Monster {
+    /// type_object is the card's row for the level it is played at
+    pub fn new(type_object: CardRef, key: u8) -> Self {
+        // sorted since the card's abilities are a set, the order must not change how monsters compare
+        let mut buffs_provided: Vec<Ability> = type_object.abilities.iter()
+            .filter(|x| x.aura_effect().is_some())
+            .copied()
+            .collect();
+        buffs_provided.sort();
         Self {
-            type_object,
             key,
             max_health: type_object.health,
             health: type_object.health,
+            max_armor: type_object.armor,
             armor: type_object.armor,
             damage: type_object.damage,
             speed: type_object.speed,
-            buffs_provided: Vec::new(),
+            buffs_provided,
+            granted_abilities: Vec::new(),
+            abilities_removed: false,
+            effects: Vec::new(),
+            type_object,
         }
     }
 
+    /// Everything compared and hashed, the card by handle
+    #[allow(clippy::type_complexity)]
+    fn state(&self) -> (*const CardData, u8, [i32; 5], Damage, &[Ability], &[Ability], bool, &[StatusEffect]) {
+        let stats = [self.max_health, self.health, self.max_armor, self.armor, self.speed];
+        (Arc::as_ptr(&self.type_object), self.key, stats, self.damage, &self.buffs_provided, &self.granted_abilities, self.abilities_removed, &self.effects)
+    }
+
     pub fn get_health(&self) -> i32 {
         self.health
     }
@@ -44,13 +81,29 @@ impl<'a> Monster<'a> {
 
     pub fn set_health_new_max(&mut self, val: i32) {
         self.set_health(val);
-        if self.health > self.max_health {
-            self.max_health = self.health;
+        let max_health = self.get_max_health();
+        if self.health > max_health {
+            self.max_health += self.health - max_health;
         }
     }
 
     pub fn get_max_health(&self) -> i32 {
-        self.max_health
+        Self::floor(self.max_health + self.modifier(&Ability::Health(0)), self.get_default_health())
+    }
+
+    /// Sets max health before status effects
+    pub fn set_max_health(&mut self, val: i32) {
+        self.max_health = val.max(0);
+        let max_health = self.get_max_health();
+        if self.health > max_health {
+            self.health = max_health;
+        }
+    }
+
+    /// Raises max health and health together, like Life Leech and Scavenger do
+    pub fn gain_max_health(&mut self, amount: i32) {
+        self.max_health += amount;
+        self.health += amount;
     }
 
     pub fn get_default_health(&self) -> i32 {
@@ -62,43 +115,98 @@ impl<'a> Monster<'a> {
     }
 
     pub fn get_speed(&self) -> i32 {
-        self.speed
+        Self::floor(self.speed + self.modifier(&Ability::Speed(0)), self.get_default_speed())
     }
 
     pub fn get_default_speed(&self) -> i32 {
         self.type_object.speed
     }
 
+    /// Sets speed before status effects
     pub fn set_speed(&mut self, speed: i32) {
         self.speed = speed;
     }
 
+    /// Damage of the first attack
     pub fn get_damage(&self) -> i32 {
-        self.damage
+        self.get_damage_for(self.get_attack_type())
     }
 
-    pub fn get_default_damage(&self) -> i32 {
-        self.type_object.damage
+    /// Damage of one attack type, 0 if the card doesn't have it
+    pub fn get_damage_for(&self, attack_type: AttackType) -> i32 {
+        let default = self.get_default_damage_for(attack_type);
+        if default <= 0 {
+            return 0;
+        }
+        let modifier = match attack_type {
+            AttackType::Melee => self.modifier(&Ability::Melee(0)),
+            AttackType::Ranged => self.modifier(&Ability::Ranged(0)),
+            AttackType::Magic => self.modifier(&Ability::Magic(0)),
+            AttackType::None => 0,
+        };
+        Self::floor(self.damage.get(attack_type) + modifier, default)
     }
 
-    pub fn set_damage(&mut self, damage: i32) {
-        self.damage = damage;
+    pub fn get_default_damage_for(&self, attack_type: AttackType) -> i32 {
+        self.type_object.damage.get(attack_type)
+    }
+
+    /// Changes one attack's damage before status effects, does nothing if the card doesn't have it
+    pub fn add_base_damage(&mut self, attack_type: AttackType, amount: i32) {
+        if self.get_default_damage_for(attack_type) <= 0 {
+            return;
+        }
+        if let Some(damage) = self.damage.get_mut(attack_type) {
+            *damage += amount;
+        }
     }
 
     pub fn get_name(&self) -> &str {
         &self.type_object.name
     }
 
+    pub fn get_rarity(&self) -> Rarity {
+        self.type_object.rarity
+    }
+
+    pub fn get_level(&self) -> u8 {
+        self.type_object.level
+    }
+
     pub fn get_key(&self) -> u8 {
         self.key
     }
     
+    /// First attack the monster makes
     pub fn get_attack_type(&self) -> AttackType {
-        self.type_object.attack_type
+        self.type_object.attack_type()
+    }
+
+    /// Every attack the monster makes in a turn, in order
+    pub fn get_attack_types(&self) -> impl Iterator<Item = AttackType> {
+        self.type_object.damage.iter_attack_types()
+    }
+
+    pub fn has_attack_type(&self, attack_type: AttackType) -> bool {
+        self.get_default_damage_for(attack_type) > 0
     }
 
     pub fn has_ability(&self, ability: Ability) -> bool {
-        self.type_object.abilities.contains(&ability)
+        self.granted_abilities.contains(&ability)
+            || (!self.abilities_removed && self.type_object.abilities.contains(&ability))
+    }
+
+    /// Gives an ability the card doesn't have, like Sneak from Super Sneak
+    pub fn grant_ability(&mut self, ability: Ability) {
+        if !self.granted_abilities.contains(&ability) {
+            self.granted_abilities.push(ability);
+        }
+    }
+
+    /// Takes away every ability the card has, including the auras it provides
+    pub fn remove_abilities(&mut self) {
+        self.abilities_removed = true;
+        self.buffs_provided.clear();
     }
 
     pub fn get_armor(&self) -> i32 {
@@ -115,9 +223,149 @@ impl<'a> Monster<'a> {
             self.armor = 0;
         }
     }
+
+    pub fn get_max_armor(&self) -> i32 {
+        (self.max_armor + self.modifier(&Ability::Armor(0))).max(0)
+    }
+
+    /// Sets max armor before status effects
+    pub fn set_max_armor(&mut self, val: i32) {
+        self.max_armor = val.max(0);
+        let max_armor = self.get_max_armor();
+        if self.armor > max_armor {
+            self.armor = max_armor;
+        }
+    }
+
+    /// Aura abilities this monster gives its team or the enemy team
+    pub fn get_buffs_provided(&self) -> &[Ability] {
+        &self.buffs_provided
+    }
+
+    pub fn get_effects(&self) -> &[StatusEffect] {
+        &self.effects
+    }
+
+    pub fn has_effect(&self, kind: Ability) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Adds effect following its stack rule, returns false if it was ignored
+    pub fn add_effect(&mut self, effect: StatusEffect) -> bool {
+        if effect.stack == StackRule::Refresh || effect.stack == StackRule::Ignore {
+            if let Some(existing) = self.effects.iter_mut().find(|x| x.kind == effect.kind) {
+                if effect.stack == StackRule::Refresh {
+                    existing.duration = effect.duration;
+                }
+                return false;
+            }
+        }
+        let maxes = (self.get_max_health(), self.get_max_armor());
+        self.effects.push(effect);
+        self.follow_maxes(maxes);
+        true
+    }
+
+    /// Takes off every effect matching f and returns them
+    pub fn remove_effects(&mut self, mut f: impl FnMut(&StatusEffect) -> bool) -> Vec<StatusEffect> {
+        let maxes = (self.get_max_health(), self.get_max_armor());
+        // only allocates when something is removed
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.effects.len() {
+            if f(&self.effects[i]) {
+                removed.push(self.effects.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        self.follow_maxes(maxes);
+        removed
+    }
+
+    /// Removes one effect of kind, returns false if there wasn't one
+    pub fn remove_effect(&mut self, kind: Ability) -> bool {
+        let Some(i) = self.effects.iter().position(|x| x.kind == kind) else {
+            return false;
+        };
+        let maxes = (self.get_max_health(), self.get_max_armor());
+        self.effects.remove(i);
+        self.follow_maxes(maxes);
+        true
+    }
+
+    /// Removes negative effects that aren't auras
+    pub fn cleanse(&mut self) -> Vec<StatusEffect> {
+        self.remove_effects(|x| x.is_removable() && x.is_negative())
+    }
+
+    /// Removes positive effects that aren't auras
+    pub fn dispel(&mut self) -> Vec<StatusEffect> {
+        self.remove_effects(|x| x.is_removable() && !x.is_negative())
+    }
+
+    /// Counts down effects for tick and returns the ones that expired
+    pub fn tick_effects(&mut self, tick: Tick) -> Vec<StatusEffect> {
+        let mut any_expired = false;
+        for effect in self.effects.iter_mut() {
+            any_expired |= effect.tick(tick);
+        }
+        if !any_expired {
+            return Vec::new();
+        }
+        self.remove_effects(|x| x.has_expired())
+    }
+
+    /// Summoner debuffs can't take a stat the card has below 1
+    pub fn apply_minimums(&mut self) {
+        for attack_type in Damage::ORDER {
+            let default = self.get_default_damage_for(attack_type);
+            if let Some(damage) = self.damage.get_mut(attack_type) {
+                if *damage <= 0 && default > 0 {
+                    *damage = 1;
+                }
+            }
+        }
+        if self.health <= 0 && self.get_default_health() > 0 {
+            self.health = 1;
+            self.max_health = self.max_health.max(1);
+        }
+        if self.speed <= 0 && self.get_default_speed() > 0 {
+            self.speed = 1;
+        }
+    }
+
+    /// Sum of the effects' changes to stat, counting each kind of shared effect once
+    fn modifier(&self, stat: &Ability) -> i32 {
+        self.effects.iter().enumerate()
+            .filter(|(i, x)| x.stack != StackRule::Shared || !self.effects[..*i].iter().any(|y| y.stack == StackRule::Shared && y.kind == x.kind))
+            .map(|(_, x)| x.modifier(stat))
+            .sum()
+    }
+
+    /// Stats the game has at least 1 of unless the card has 0
+    fn floor(value: i32, default: i32) -> i32 {
+        if default > 0 {
+            value.max(1)
+        } else {
+            value.max(0)
+        }
+    }
+
+    /// Moves health and armor with their maxes, an alive monster keeps at least 1 health
+    fn follow_maxes(&mut self, (max_health, max_armor): (i32, i32)) {
+        let health_delta = self.get_max_health() - max_health;
+        if health_delta != 0 && self.is_alive() {
+            self.health = (self.health + health_delta).clamp(1, self.get_max_health().max(1));
+        }
+        let armor_delta = self.get_max_armor() - max_armor;
+        if armor_delta != 0 {
+            self.armor = (self.armor + armor_delta).clamp(0, self.get_max_armor());
+        }
+    }
 }
 
-impl<'a> Display for Monster<'a> {
+impl Display for Monster {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut string = format!("{} ({}/{})", self.get_name(), self.get_health(), self.get_max_health());
         if self.get_armor() > 0 {
@@ -127,7 +375,7 @@ impl<'a> Display for Monster<'a> {
     }
 }
 
-// impl Ord for Monster<'a> {
+// impl Ord for Monster {
 //     fn cmp(&self, other: &Self) -> Ordering {
 //         self.get_speed().cmp(&other.get_speed())
 //     }
@@ -146,3 +394,30 @@ impl<'a> Display for Monster<'a> {
 // }
 
 // impl Eq for Monster {}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use crate::{cardparse::enums::Ability, gamedata::registry::Registry};
+
+    use super::Monster;
+
+    #[test]
+    fn test_buffs_provided_order() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let card = reg.get_card("Tarsa").unwrap();
+        let auras = [Ability::Slow, Ability::Inspire, Ability::Weaken, Ability::Protect, Ability::Demoralize];
+        // each set hashes with its own keys, so they iterate in different orders
+        let monsters: Vec<_> = (0..8).map(|_| {
+            let mut data = (**card).clone();
+            data.abilities = auras.iter().copied().collect::<HashSet<_>>();
+            Monster::new(Arc::new(data), 0)
+        }).collect();
+        let mut expected = auras.to_vec();
+        expected.sort();
+        for mons in &monsters {
+            assert_eq!(mons.get_buffs_provided(), expected);
+        }
+    }
+}
@@ -1,14 +1,191 @@
-use std::{collections::HashMap, ops::Deref};
+use std::{collections::{BTreeMap, HashMap}, fs, sync::Arc};
 
-use crate::{cardparse::{carddata::CardData, cardparser::get_map, enums::Element}};
-use crate::battles::battle::Battle;
+use csv::Reader;
+
+use crate::cardparse::{carddata::{CardData, CardRef}, carderror::{CardError, CardReport, CardWarning, UnknownCard}, cardjson, cardparser::{self, get_cards, parse_cards, parse_patch, CardRow}, enums::Role};
+
+/// Most suggestions an UnknownCard lists
+pub const MAX_SUGGESTIONS: usize = 3;
 
 #[derive(Debug)]
 pub struct Registry {
-    pub map: HashMap<String, CardData>,
+    /// Each card at its lowest level
+    pub map: HashMap<String, CardRef>,
+    /// Rows for the levels above the one in map
+    pub levels: HashMap<String, BTreeMap<u8, CardRef>>,
+    /// Lowercase names and aliases to the card's name in map
+    pub names: HashMap<String, String>,
+    /// Cards redefined by a later set and cells two patches disagree on
+    pub conflicts: Vec<CardWarning>,
+    /// Value, file and line of every patched cell, keyed by card, level and column
+    pub patched: HashMap<(String, u8, String), (String, String, u64)>,
 }
 
 impl<'a> Registry {
+    /// Loads a csv or json card file, the error says which file, line, column and value broke it
+    pub fn load(path: &str) -> Result<Self, CardError> {
+        if is_json(path) {
+            return Ok(cardjson::read_cards(path)?.into());
+        }
+        Ok(get_cards(path)?.into())
+    }
+
+    /// Writes every card at every level, as json if path ends in .json and csv otherwise
+    pub fn save(&self, path: &str) -> Result<(), CardError> {
+        let mut rows: Vec<&CardData> = self.map.values()
+            .chain(self.levels.values().flat_map(|rows| rows.values()))
+            .map(|card| card.as_ref())
+            .collect();
+        rows.sort_by(|a, b| (&a.name, a.level).cmp(&(&b.name, b.level)));
+        if is_json(path) {
+            return cardjson::write_cards(path, rows);
+        }
+        cardparser::write_cards(path, rows)
+    }
+
+    /// Turns a card file into the other format, eg csv into json
+    pub fn convert(from: &str, to: &str) -> Result<(), CardError> {
+        Self::load(from)?.save(to)
+    }
+
+    /// Stacks card sets, later sets replace the cards they redefine, then applies the patches in order
+    pub fn stack(sets: &[&str], patches: &[&str]) -> Result<Self, CardError> {
+        let mut reg = Self::from(Vec::new());
+        for path in sets {
+            reg.add_set(path)?;
+        }
+        for path in patches {
+            reg.apply_patch(path)?;
+        }
+        Ok(reg)
+    }
+
+    /// Adds the cards in path, a card an earlier file defined is replaced and reported as a conflict
+    pub fn add_set(&mut self, path: &str) -> Result<(), CardError> {
+        let rows: Vec<CardRow> = if is_json(path) {
+            // json rows don't keep their line
+            cardjson::read_cards(path)?.into_iter().map(|card| (0, card)).collect()
+        } else {
+            parse_cards(path)?.into_iter().collect::<Result<_, _>>()?
+        };
+        let mut first_line: HashMap<String, u64> = HashMap::new();
+        let mut cards = Vec::new();
+        for (line, card) in rows {
+            first_line.entry(card.name.to_owned()).or_insert(line);
+            cards.push(card);
+        }
+        let mut redefined: Vec<(&String, &u64)> = first_line.iter().filter(|(name, _)| self.map.contains_key(*name)).collect();
+        redefined.sort_by_key(|(_, line)| **line);
+        for (name, line) in redefined {
+            let old = self.map.remove(name).expect("name was checked");
+            self.levels.remove(name);
+            self.conflicts.push(CardWarning {
+                file: path.to_owned(),
+                line: *line,
+                card: name.to_owned(),
+                message: format!("redefines the card from {}", old.sources.join(", ")),
+            });
+        }
+        self.insert_rows(cards);
+        Ok(())
+    }
+
+    /// Applies a balance patch, see cardparser::parse_patch for the format
+    pub fn apply_patch(&mut self, path: &str) -> Result<(), CardError> {
+        for row in parse_patch(path)? {
+            let name = self.resolve(&row.card)
+                .map_err(|e| CardError::Field {
+                    file: path.to_owned(),
+                    line: row.line,
+                    column: "Card".to_owned(),
+                    value: row.card.to_owned(),
+                    message: e.to_string(),
+                })?
+                .to_owned();
+            // battles already holding a row keep the stats it had when they started
+            let mut cards: Vec<&mut CardData> = self.map.get_mut(&name).into_iter()
+                .chain(self.levels.get_mut(&name).into_iter().flat_map(|rows| rows.values_mut()))
+                .map(Arc::make_mut)
+                .filter(|card| row.level.is_none_or(|level| card.level == level))
+                .collect();
+            if cards.is_empty() {
+                let level = row.level.unwrap_or_default();
+                return Err(CardError::Field {
+                    file: path.to_owned(),
+                    line: row.line,
+                    column: "Level".to_owned(),
+                    value: level.to_string(),
+                    message: format!("{} has no level {} row", name, level),
+                });
+            }
+            for card in cards.iter_mut() {
+                card.patch(&row.changes).map_err(|e| CardError::field(path, row.line, e))?;
+                if card.sources.last().map(|x| x.as_str()) != Some(path) {
+                    card.sources.push(path.to_owned());
+                }
+                for (column, value) in &row.changes {
+                    let key = (name.to_owned(), card.level, column.to_owned());
+                    let cell = (value.to_owned(), path.to_owned(), row.line);
+                    if let Some((old_value, old_file, old_line)) = self.patched.insert(key, cell) {
+                        if old_value != *value {
+                            self.conflicts.push(CardWarning {
+                                file: path.to_owned(),
+                                line: row.line,
+                                card: name.to_owned(),
+                                message: format!("level {} {} is patched to '{}' after {}:{} patched it to '{}'",
+                                    card.level, column, value, old_file, old_line, old_value),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every error and warning in a card file at once instead of stopping at the first
+    pub fn validate(path: &str) -> CardReport {
+        let mut report = CardReport::default();
+        let rows = if is_json(path) {
+            cardjson::read_cards(path).map(|cards| cards.into_iter().map(|card| Ok((0, card))).collect())
+        } else {
+            parse_cards(path)
+        };
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                report.errors.push(e);
+                return report;
+            },
+        };
+
+        let mut seen: HashMap<(String, u8), u64> = HashMap::new();
+        for row in rows {
+            let (line, card) = match row {
+                Ok(row) => row,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                },
+            };
+            let mut warn = |message: String| report.warnings.push(CardWarning {
+                file: path.to_owned(),
+                line,
+                card: card.name.to_owned(),
+                message,
+            });
+            if let Some(first) = seen.insert((card.name.to_owned(), card.level), line) {
+                warn(format!("level {} is a duplicate of line {}", card.level, first));
+            }
+            match card.role {
+                Role::Summoner if !card.damage.attack_types().is_empty() => warn("is a summoner with non-zero damage".to_owned()),
+                Role::Monster if card.health <= 0 => warn("is a monster with no health".to_owned()),
+                _ => (),
+            }
+        }
+        report
+    }
+
     // pub fn get_element(&'a self, elem: Element) -> Vec<&'a str> {
     //     let res = self.map.iter().filter_map(|(name, card)| {
     //         if card.element == elem {
@@ -20,37 +197,321 @@ impl<'a> Registry {
     //     res
     // }
 
+    /// Names of matching cards in name order
     pub fn query(&'a self, filter: impl Fn(&CardData) -> bool) -> Vec<&'a str> {
-        self.map.iter().filter_map(|(name, card)| {
-            if filter(card) {
-                Some(name.as_ref())
-            } else {
-                None
-            }
-        }).collect()
+        self.filter(filter).into_iter().map(|(name, _)| name).collect()
     }
 
-    pub fn filter(&'a self, filter: impl Fn(&CardData) -> bool) -> Vec<(&'a str, &'a CardData)> {
-        self.map.iter().filter_map(|(name, card)| {
+    /// Matching cards in name order, not map order, so seeded runs see them the same way every time
+    pub fn filter(&'a self, filter: impl Fn(&CardData) -> bool) -> Vec<(&'a str, &'a CardRef)> {
+        let mut res: Vec<_> = self.map.iter().filter_map(|(name, card)| {
             if filter(card) {
                 return Some((name.as_ref(), card))
             }
             None
-        }).collect()
+        }).collect();
+        res.sort_unstable_by_key(|(name, _)| *name);
+        res
+    }
+
+    /// Card name for a name in any case or an alias
+    pub fn resolve(&'a self, name: &str) -> Result<&'a str, UnknownCard> {
+        if let Some((name, _)) = self.map.get_key_value(name) {
+            return Ok(name);
+        }
+        match self.names.get(&name.trim().to_lowercase()) {
+            Some(name) => Ok(name),
+            None => Err(UnknownCard { name: name.to_owned(), suggestions: self.suggest(name), level: None }),
+        }
+    }
+
+    /// Closest card names and aliases to name by edit distance
+    pub fn suggest(&self, name: &str) -> Vec<String> {
+        let name = name.trim().to_lowercase();
+        let max_distance = (name.chars().count() / 3).max(2);
+        let mut close: Vec<(usize, &String)> = self.names.iter()
+            .map(|(key, card)| (edit_distance(&name, key), card))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        close.sort();
+        let mut suggestions: Vec<String> = Vec::new();
+        for (_, card) in close {
+            if !suggestions.contains(card) {
+                suggestions.push(card.to_owned());
+            }
+        }
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+
+    /// Lets alias stand in for the card name in decks
+    pub fn add_alias(&mut self, alias: &str, name: &str) -> Result<(), UnknownCard> {
+        let name = self.resolve(name)?.to_owned();
+        self.names.insert(alias.trim().to_lowercase(), name);
+        Ok(())
+    }
+
+    /// Reads aliases from a csv with Alias and Card columns
+    pub fn load_aliases(&mut self, path: &str) -> Result<(), CardError> {
+        let content = fs::read_to_string(path).map_err(|e| CardError::Io { file: path.to_owned(), message: e.to_string() })?;
+        let mut reader = Reader::from_reader(content.as_bytes());
+        for record in reader.records() {
+            let record = record.map_err(|e| CardError::Row {
+                file: path.to_owned(),
+                line: e.position().map(|pos| pos.line()).unwrap_or_default(),
+                message: e.to_string(),
+            })?;
+            let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+            let (alias, name) = (record.get(0).unwrap_or_default(), record.get(1).unwrap_or_default());
+            self.add_alias(alias, name).map_err(|e| CardError::Field {
+                file: path.to_owned(),
+                line,
+                column: "Card".to_owned(),
+                value: name.to_owned(),
+                message: e.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Adds rows for cards the registry doesn't have yet, the lowest level of each goes in map
+    fn insert_rows(&mut self, cards: Vec<CardData>) {
+        let mut rows: HashMap<String, BTreeMap<u8, CardRef>> = HashMap::new();
+        for card in cards {
+            rows.entry(card.name.to_owned()).or_default().insert(card.level, Arc::new(card));
+        }
+        for (name, mut by_level) in rows {
+            let base = by_level.pop_first().expect("every card has a row").1;
+            self.names.insert(name.to_lowercase(), name.to_owned());
+            self.map.insert(name.to_owned(), base);
+            if !by_level.is_empty() {
+                self.levels.insert(name, by_level);
+            }
+        }
+    }
+
+    fn index_names(map: &HashMap<String, CardRef>) -> HashMap<String, String> {
+        map.keys().map(|name| (name.to_lowercase(), name.to_owned())).collect()
+    }
+
+    /// Level of the highest row name has
+    pub fn max_level(&self, name: &str) -> Option<u8> {
+        match self.levels.get(name).and_then(|rows| rows.last_key_value()) {
+            Some((level, _)) => Some(*level),
+            None => self.map.get(name).map(|card| card.level),
+        }
+    }
+
+    /// Stats for name at level, taken from the highest row at or below level,
+    /// None for level 0 or a level above name's highest row
+    pub fn get_level(&'a self, name: &str, level: u8) -> Option<&'a CardRef> {
+        if level == 0 || level > self.max_level(name)? {
+            return None;
+        }
+        let higher = self.levels.get(name).and_then(|rows| rows.range(..=level).next_back());
+        if let Some((_, card)) = higher {
+            return Some(card);
+        }
+        self.map.get(name)
+    }
+
+    /// Looks up a deck entry like "Kobold Bruiser", "kobold bruiser" or "Kobold Bruiser:3"
+    pub fn get_card(&'a self, entry: &str) -> Result<&'a CardRef, UnknownCard> {
+        let (name, level) = Self::parse_entry(entry);
+        let name = self.resolve(name)?;
+        self.get_level(name, level).ok_or_else(|| UnknownCard {
+            name: name.to_owned(),
+            suggestions: Vec::new(),
+            level: Some((level, self.max_level(name).expect("resolved names are in map"))),
+        })
+    }
+
+    /// Splits a deck entry into its name and level, level 1 if it doesn't name one
+    pub fn parse_entry(entry: &str) -> (&str, u8) {
+        if let Some((name, level)) = entry.rsplit_once(':') {
+            if let Ok(level) = level.trim().parse() {
+                return (name.trim(), level);
+            }
+        }
+        (entry, 1)
     }
 }
 
+/// Panics on a bad file, so only for test fixtures, everything else uses Registry::load
+#[cfg(test)]
 impl From<&str> for Registry {
     fn from(filename: &str) -> Self {
-        let map = get_map(filename).expect("file not found");
-        map.into()
+        Self::load(filename).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl From<Vec<CardData>> for Registry {
+    fn from(cards: Vec<CardData>) -> Self {
+        let mut reg = Self::from(HashMap::new());
+        reg.insert_rows(cards);
+        reg
     }
 }
 
 impl From<HashMap<String, CardData>> for Registry {
     fn from(map: HashMap<String, CardData>) -> Self {
+        let map: HashMap<String, CardRef> = map.into_iter().map(|(name, card)| (name, Arc::new(card))).collect();
         Self {
+            names: Self::index_names(&map),
             map,
+            levels: HashMap::new(),
+            conflicts: Vec::new(),
+            patched: HashMap::new(),
+        }
+    }
+}
+
+fn is_json(path: &str) -> bool {
+    path.to_lowercase().ends_with(".json")
+}
+
+/// Levenshtein distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let swap = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = swap.min(prev[j + 1] + 1).min(cur[j] + 1);
         }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let reg = Registry::from("assets/leveled_cards.csv");
+        assert_eq!(reg.get_card("Kobold Bruiser").unwrap().health, 3);
+        assert_eq!(reg.get_card("Kobold Bruiser:3").unwrap().health, 4);
+        // no row for level 4, so it keeps the level 3 stats
+        assert_eq!(reg.get_card("Kobold Bruiser:4").unwrap().level, 3);
+        assert!(reg.get_card("Kobold Bruiser:5").unwrap().abilities.contains(&crate::cardparse::enums::Ability::Reach));
+        // levels past the highest row, and level 0, don't exist
+        let err = reg.get_card("Goblin Fireballer:7").unwrap_err();
+        assert_eq!(err.level, Some((7, 1)));
+        assert_eq!(err.to_string(), "card 'Goblin Fireballer' has no level 7, its levels are 1 to 1");
+        assert_eq!(reg.get_card("kobold bruiser:6").unwrap_err().level, Some((6, 5)));
+        assert_eq!(reg.get_card("Kobold Bruiser:0").unwrap_err().level, Some((0, 5)));
+        assert_eq!(Registry::parse_entry("Magma Troll : 2"), ("Magma Troll", 2));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let err = Registry::load("assets/missing.csv").unwrap_err();
+        assert!(matches!(err, CardError::Io { .. }));
+
+        let err = Registry::load("assets/bad_cards.csv").unwrap_err();
+        let exp = CardError::Field {
+            file: "assets/bad_cards.csv".to_owned(),
+            line: 3,
+            column: "Element".to_owned(),
+            value: "lava".to_owned(),
+            message: "'lava' is not a known Element".to_owned(),
+        };
+        assert_eq!(err, exp);
+        assert_eq!(err.to_string(), "assets/bad_cards.csv:3: column Element has bad value 'lava': 'lava' is not a known Element");
+    }
+
+    #[test]
+    fn test_validate() {
+        let report = Registry::validate("assets/bad_cards.csv");
+        let errors: Vec<(u64, &str)> = report.errors.iter().map(|e| match e {
+            CardError::Field { line, column, .. } => (*line, column.as_str()),
+            CardError::Row { line, .. } => (*line, ""),
+            CardError::Io { .. } => (0, ""),
+        }).collect();
+        assert_eq!(errors, vec![(3, "Element"), (4, "Dmg"), (5, "Ability2"), (6, "")]);
+
+        let warnings: Vec<(u64, &str)> = report.warnings.iter().map(|w| (w.line, w.card.as_str())).collect();
+        assert_eq!(warnings, vec![(7, "Pyre"), (8, "Kobold Bruiser")]);
+        assert!(!report.is_ok());
+        assert!(Registry::validate("assets/new_cards.csv").is_ok());
+    }
+
+    #[test]
+    fn test_names() {
+        let mut reg = Registry::from("assets/new_cards.csv");
+        assert_eq!(reg.get_card("magma TROLL").unwrap().name, "Magma Troll");
+        assert_eq!(reg.resolve(" kelya frendul ").unwrap(), "Kelya Frendul");
+
+        reg.load_aliases("assets/aliases.csv").unwrap();
+        assert_eq!(reg.resolve("troll").unwrap(), "Magma Troll");
+        assert_eq!(reg.get_card("Spy:1").unwrap().name, "Serpentine Spy");
+        assert!(reg.add_alias("Bob", "Nobody").is_err());
+
+        let err = reg.get_card("Magma Trol").unwrap_err();
+        assert_eq!(err.suggestions[0], "Magma Troll");
+        assert!(err.to_string().starts_with("card 'Magma Trol' not found in registry, did you mean Magma Troll"));
+        assert!(reg.suggest("zzzzzzzz").is_empty());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_json() {
+        let path = std::env::temp_dir().join("splint_test_json.json");
+        let path = path.to_str().unwrap();
+        let csv = Registry::from("assets/leveled_cards.csv");
+        csv.save(path).unwrap();
+        let json = Registry::from(path);
+        for (name, card) in &csv.map {
+            assert_eq!(json.map[name].sources, vec![path]);
+            assert_eq!(CardData { sources: Vec::new(), ..json.map[name].as_ref().clone() }, CardData { sources: Vec::new(), ..card.as_ref().clone() });
+        }
+        assert_eq!(json.levels.len(), csv.levels.len());
+        assert_eq!(json.get_card("Kobold Bruiser:5").unwrap().abilities, csv.get_card("Kobold Bruiser:5").unwrap().abilities);
+
+        let csv_path = path.replace(".json", "_back.csv");
+        Registry::convert(path, &csv_path).unwrap();
+        let back = Registry::from(csv_path.as_str());
+        assert_eq!(back.map["Battle Mage"].damage, csv.map["Battle Mage"].damage);
+        assert_eq!(back.map["Contessa L'ament"].abilities, csv.map["Contessa L'ament"].abilities);
+
+        let golem = Registry::from("assets/json_cards.json");
+        assert_eq!(golem.get_card("Chain Golem:3").unwrap().abilities.len(), 4);
+        assert_eq!(golem.map["Chain Golem"].damage.attack_types().len(), 2);
+        let err = golem.save(&path.replace(".json", ".csv")).unwrap_err();
+        assert!(matches!(err, CardError::Field { line: 2, .. }), "{}", err);
+
+        let err = Registry::load("assets/bad_cards.json").unwrap_err();
+        assert!(matches!(err, CardError::Row { line: 2, .. }), "{}", err);
+        assert!(err.to_string().contains("'lava' is not a known Element"));
+    }
+
+    #[test]
+    fn test_stack() {
+        use crate::cardparse::enums::{Ability, AttackType};
+
+        let reg = Registry::stack(&["assets/cards.csv", "assets/new_cards.csv"], &["assets/patches/balance.csv", "assets/patches/conflict.csv"]).unwrap();
+        assert_eq!(reg.map["Goblin Shaman"].sources, vec!["assets/cards.csv"]);
+        assert_eq!(reg.map["Pyre"].sources, vec!["assets/new_cards.csv"]);
+        let redefined = reg.conflicts.iter().find(|x| x.card == "Pyre").unwrap();
+        assert_eq!(redefined.message, "redefines the card from assets/cards.csv");
+
+        let troll = &reg.map["Magma Troll"];
+        assert_eq!(troll.health, 4);
+        assert_eq!(troll.sources, vec!["assets/new_cards.csv", "assets/patches/balance.csv", "assets/patches/conflict.csv"]);
+        let spy = &reg.map["Serpentine Spy"];
+        assert_eq!((spy.attack_type(), spy.damage.get(AttackType::Ranged), spy.damage.melee), (AttackType::Ranged, 1, 0));
+        assert!(spy.abilities.is_empty());
+        assert!(reg.map["Kobold Bruiser"].abilities.contains(&Ability::Taunt));
+        assert_eq!(reg.map["Kobold Bruiser"].speed, 3);
+
+        let patch_conflicts: Vec<&CardWarning> = reg.conflicts.iter().filter(|x| x.file == "assets/patches/conflict.csv").collect();
+        assert_eq!(patch_conflicts.len(), 1);
+        assert_eq!(patch_conflicts[0].to_string(), "assets/patches/conflict.csv:2: warning: Magma Troll level 1 Health is patched to '4' after assets/patches/balance.csv:2 patched it to '3'");
+
+        let err = Registry::stack(&["assets/new_cards.csv"], &["assets/patches/conflict.csv", "assets/aliases.csv"]).unwrap_err();
+        assert_eq!(err, CardError::Row { file: "assets/aliases.csv".to_owned(), line: 1, message: "unknown column Alias".to_owned() });
     }
 }
@@ -0,0 +1,132 @@
+use crate::{battles::monsterkey::MonsterKey, cardparse::enums::Ability};
+
+/// How long a status effect stays on a monster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Duration {
+    /// Counts down once per tick and expires at 0
+    Rounds(u32),
+    /// Stays until something takes it off, like a stun being served
+    UntilRemoved,
+    /// Stays until its source dies, can't be cleansed or dispelled
+    WhileSourceAlive,
+}
+
+/// What happens when an effect of the same kind is already on the monster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StackRule {
+    /// Both stay on the monster
+    Stack,
+    /// The existing one takes the new duration
+    Refresh,
+    /// The new one is dropped
+    Ignore,
+    /// Both stay on the monster but only one counts, so it lasts while either source does
+    Shared,
+}
+
+/// Point in the round where an effect counts down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tick {
+    RoundStart,
+    RoundEnd,
+}
+
+/// An effect on a monster, stat changes are read through by Monster's getters
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StatusEffect {
+    /// Stat abilities like Health(-1) change that stat, anything else is a flag like Stun
+    pub kind: Ability,
+    pub source: Option<MonsterKey>,
+    pub duration: Duration,
+    pub stack: StackRule,
+    pub tick: Tick,
+}
+
+impl StatusEffect {
+    pub fn new(kind: Ability, source: Option<MonsterKey>, duration: Duration, stack: StackRule, tick: Tick) -> Self {
+        Self {
+            kind,
+            source,
+            duration,
+            stack,
+            tick,
+        }
+    }
+
+    /// A stat change that lasts as long as a monster providing it, the same aura from 2 monsters counts once
+    pub fn aura(stat: Ability, source: MonsterKey) -> Self {
+        Self::new(stat, Some(source), Duration::WhileSourceAlive, StackRule::Shared, Tick::RoundEnd)
+    }
+
+    /// Loses the next turn, a second stun before then does nothing
+    pub fn stun(source: MonsterKey) -> Self {
+        Self::new(Ability::Stun, Some(source), Duration::UntilRemoved, StackRule::Ignore, Tick::RoundEnd)
+    }
+
+    /// Stat change this effect makes to the stat ability, 0 if it doesn't touch it
+    pub fn modifier(&self, stat: &Ability) -> i32 {
+        match (self.kind, stat) {
+            (Ability::Health(i), Ability::Health(_))
+            | (Ability::Armor(i), Ability::Armor(_))
+            | (Ability::Speed(i), Ability::Speed(_))
+            | (Ability::Melee(i), Ability::Melee(_))
+            | (Ability::Ranged(i), Ability::Ranged(_))
+            | (Ability::Magic(i), Ability::Magic(_)) => i as i32,
+            _ => 0,
+        }
+    }
+
+    /// Stat debuffs and flags like Stun, what Cleanse removes
+    pub fn is_negative(&self) -> bool {
+        match self.kind {
+            Ability::Health(_) | Ability::Armor(_) | Ability::Speed(_)
+            | Ability::Melee(_) | Ability::Ranged(_) | Ability::Magic(_) => !self.kind.is_buff(),
+            _ => true,
+        }
+    }
+
+    /// Auras only go away with their source
+    pub fn is_removable(&self) -> bool {
+        self.duration != Duration::WhileSourceAlive
+    }
+
+    /// Counts down a timed effect if tick is its phase, returns true once it has expired
+    pub fn tick(&mut self, tick: Tick) -> bool {
+        if self.tick != tick {
+            return false;
+        }
+        match &mut self.duration {
+            Duration::Rounds(n) => {
+                *n = n.saturating_sub(1);
+                *n == 0
+            },
+            _ => false,
+        }
+    }
+
+    /// A timed effect that has run out
+    pub fn has_expired(&self) -> bool {
+        self.duration == Duration::Rounds(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_and_modifier() {
+        let mut effect = StatusEffect::new(Ability::Speed(-1), None, Duration::Rounds(2), StackRule::Refresh, Tick::RoundStart);
+        assert_eq!(effect.modifier(&Ability::Speed(0)), -1);
+        assert_eq!(effect.modifier(&Ability::Health(0)), 0);
+        assert!(effect.is_negative());
+
+        assert!(!effect.tick(Tick::RoundEnd));
+        assert!(!effect.tick(Tick::RoundStart));
+        assert!(effect.tick(Tick::RoundStart));
+
+        let aura = StatusEffect::aura(Ability::Armor(2), MonsterKey::Home(0));
+        assert!(!aura.is_negative());
+        assert!(!aura.is_removable());
+    }
+}
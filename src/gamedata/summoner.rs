@@ -1,14 +1,29 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, hash::{Hash, Hasher}, sync::Arc};
 
-use crate::cardparse::{carddata::CardData, enums::Ability};
+use crate::cardparse::{carddata::CardRef, enums::Ability};
 
-#[derive(Debug)]
-pub struct Summoner<'a> {
-    type_object: &'a CardData,
+#[derive(Debug, Clone)]
+pub struct Summoner {
+    type_object: CardRef,
 }
 
-impl<'a> Summoner<'a> {
-    pub fn new(type_object: &'a CardData) -> Self {
+/// Summoners never change in a battle, the same card handle is the same summoner
+impl PartialEq for Summoner {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.type_object, &other.type_object)
+    }
+}
+
+impl Eq for Summoner {}
+
+impl Hash for Summoner {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.type_object).hash(state);
+    }
+}
+
+impl Summoner {
+    pub fn new(type_object: CardRef) -> Self {
         Self {
             type_object,
         }
@@ -26,7 +41,7 @@ impl<'a> Summoner<'a> {
         self
         .iter_abilities()
         .filter(|x| x.is_buff())
-        .map(|x| x.clone())
+        .copied()
         .collect()
     }
 
@@ -34,7 +49,7 @@ impl<'a> Summoner<'a> {
         self
         .iter_abilities()
         .filter(|x| x.is_debuff())
-        .map(|x| x.clone())
+        .copied()
         .collect()
     }
 }
@@ -0,0 +1,4 @@
+pub mod gamedata;
+pub mod battles;
+pub mod cardparse;
+pub mod tourney;
@@ -1,22 +1,22 @@
-#![allow(non_snake_case)]
+use std::collections::HashMap;
 
-use std::collections::{HashSet, HashMap};
+use splinter_key::cardparse::enums::Element;
+use splinter_key::gamedata::{deck::{Deck, DeckRules, read_decks}, registry::Registry};
+use splinter_key::tourney::{combos::{tournament, super_tournament, Training}, matchup::{Matchup, MatchupOptions}, exact::{ExactOutcomes, MAX_STATES}};
 
-use cardparse::enums::Element;
-use gamedata::registry::Registry;
-use tourney::combos::{tournament, super_tournament};
-
-use crate::{battles::battle::Battle, cardparse::enums::Ability};
+use splinter_key::{battles::{battle::Battle, battleevent::PrintSink, ruleset::Ruleset}, cardparse::enums::Ability};
 use clappers::Clappers;
 
-
-mod gamedata;
-mod battles;
-mod cardparse;
-mod tourney;
+/// Loads a card file, or reports why it couldn't and exits
+fn load_cards(path: &str) -> Registry {
+    Registry::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
 pub fn unique_abilities() {
-    let registry = Registry::from("assets/new_cards.csv");
+    let registry = load_cards("assets/new_cards.csv");
     let mut all_abilities: HashMap<Ability, i32> = HashMap::new();
     for card in registry.map.values() {
         for ability in &card.abilities {
@@ -25,7 +25,7 @@ pub fn unique_abilities() {
         }
     }
     let mut as_vec: Vec<_> = all_abilities.into_iter().collect();
-    as_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    as_vec.sort_by_key(|a| std::cmp::Reverse(a.1));
     // println!("{:?}", all_abilities);
     as_vec.into_iter().for_each(|(ability, count)| {
         println!("{:?}: {}", ability, count);
@@ -33,29 +33,61 @@ pub fn unique_abilities() {
 }
 
 pub fn example_battle() {
-    let registry = Registry::from("assets/cards.csv");
+    let registry = load_cards("assets/cards.csv");
 
     let home = vec!["Drake of Arnak", "Goblin Shaman", "Fire Beetle"];
     let oppo = vec!["Pyre", "Spineback Turtle", "Kobold Bruiser"];
 
-    let mut battle = Battle::new(&registry, &home, &oppo);
-    battle.game();
+    let home = Deck::new(&registry, &home).unwrap_or_else(|e| panic!("{}", e));
+    // Spineback Turtle is water, so this one skips the element check
+    let oppo = Deck::with_rules(&registry, &oppo, DeckRules::sandbox()).unwrap_or_else(|e| panic!("{}", e));
+    let mut battle = Battle::new(&home, &oppo);
+    battle.game_with_sink(&mut PrintSink);
 }
 
 pub fn example_battle_2() {
-    let reg = Registry::from("assets/new_cards.csv");
+    let reg = load_cards("assets/new_cards.csv");
     let home = vec!["Tarsa", "Living Lava", "Magma Troll", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
     let oppo = vec!["Kelya Frendul", "Serpent of Eld", "Feasting Seaweed", "Sniping Narwhal", "Ice Pixie"];
-    let mut battle = Battle::new(&reg, &home, &oppo);
-    battle.game();
+    let home = Deck::new(&reg, &home).unwrap_or_else(|e| panic!("{}", e));
+    let oppo = Deck::new(&reg, &oppo).unwrap_or_else(|e| panic!("{}", e));
+    let mut battle = Battle::new(&home, &oppo);
+    battle.game_with_sink(&mut PrintSink);
 }
 
 pub fn example_battle_3() {
-    let reg = Registry::from("assets/new_cards.csv");
+    let reg = load_cards("assets/new_cards.csv");
     let home = vec!["Tarsa", "Living Lava", "Venari Spellsmith", "Tenyii Striker", "Serpentine Spy", "Lava Spider"];
     let oppo = vec!["Obsidian", "Venari Knifer", "Mycelic Slipspawn", "Goblin Tower"];
-    let mut battle = Battle::new(&reg, &home, &oppo);
-    battle.game();
+    let home = Deck::new(&reg, &home).unwrap_or_else(|e| panic!("{}", e));
+    let oppo = Deck::new(&reg, &oppo).unwrap_or_else(|e| panic!("{}", e));
+    let mut battle = Battle::new(&home, &oppo);
+    battle.game_with_sink(&mut PrintSink);
+}
+
+/// Plays the two decks in path
+pub fn deck_battle(reg: &Registry, path: &str) -> Result<(), String> {
+    let (home, oppo) = read_decks(reg, path)?;
+    let mut battle = Battle::new(&home, &oppo);
+    battle.game_with_sink(&mut PrintSink);
+    Ok(())
+}
+
+/// Plays the two decks in path many times and prints how often home wins
+pub fn deck_matchup(reg: &Registry, path: &str, rulesets: &[Ruleset], options: &MatchupOptions) -> Result<(), String> {
+    let (home, oppo) = read_decks(reg, path)?;
+    println!("{}\nvs\n{}", home, oppo);
+    println!("{}", Matchup::evaluate(&home, &oppo, rulesets, options));
+    Ok(())
+}
+
+/// Follows every chance event between the two decks in path and prints the exact win/draw/loss chances
+pub fn deck_outcomes(reg: &Registry, path: &str, rulesets: &[Ruleset], max_states: usize) -> Result<(), String> {
+    let (home, oppo) = read_decks(reg, path)?;
+    println!("{}\nvs\n{}", home, oppo);
+    let outcomes = ExactOutcomes::evaluate(&home, &oppo, rulesets, max_states).map_err(|e| e.to_string())?;
+    println!("{}", outcomes);
+    Ok(())
 }
 
 fn main() {
@@ -78,6 +110,20 @@ fn main() {
             "m|mana",
             "n|lines",
             "t|train",
+            "r|rulesets",
+            "v|validate",
+            "a|aliases",
+            "c|cards",
+            "p|patches",
+            "b|battle",
+            "x|convert",
+            "j|threads",
+            "s|seed",
+            "u|matchup",
+            "g|games",
+            "w|width",
+            "o|outcomes",
+            "k|max-states",
         ])
         .parse();
 
@@ -92,9 +138,103 @@ fn main() {
                 -m|--mana max_mana               Select max mana
                 -n|--lines                       # of lines of output
                 -t|--train                       # of battles to determine Elo
+                -j|--threads threads             Worker threads for training, default is one per core
+                -s|--seed seed                   Seed training battles so the Elo table can be reproduced
+                -v|--validate file               List every problem in a card file and exit
+                -r|--rulesets rulesets           Comma separated rulesets, eg \"earthquake,super sneak\"
+                -c|--cards files                 Comma separated card sets, later ones replace cards, default assets/new_cards.csv
+                -p|--patches files               Comma separated balance patches applied in order on top of the cards
+                -a|--aliases file                Card name aliases, default assets/aliases.csv
+                -x|--convert files               Convert a card file between csv and json and exit, eg \"cards.csv,cards.json\"
+                -b|--battle file                 Play the 2 decks in file, one per line, and exit
+                -u|--matchup file                Play the 2 decks in file many times, print win/draw/loss rates and exit
+                -g|--games games                 Most games --matchup plays, default 1000
+                -w|--width width                 Stop --matchup once the 95% win rate interval is this narrow, eg 0.05
+                -o|--outcomes file               Print the exact win/draw/loss chances of the 2 decks in file and exit, for small battles
+                -k|--max-states states           Give up on --outcomes past this many distinct states, default 200000
         ");
     }
 
+    let validate = clappers.get_single("validate");
+    if !validate.is_empty() {
+        let report = Registry::validate(&validate);
+        println!("{}", report);
+        if !report.is_ok() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let convert = clappers.get_single("convert");
+    if !convert.is_empty() {
+        let Some((from, to)) = convert.split_once(',') else {
+            eprintln!("--convert takes 2 files, eg \"cards.csv,cards.json\"");
+            std::process::exit(1);
+        };
+        if let Err(e) = Registry::convert(from.trim(), to.trim()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let cards = clappers.get_single("cards");
+    let cards = if cards.is_empty() { "assets/new_cards.csv".to_owned() } else { cards };
+    let sets: Vec<&str> = cards.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
+    let patches = clappers.get_single("patches");
+    let patches: Vec<&str> = patches.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
+    let mut reg = Registry::stack(&sets, &patches).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    for conflict in &reg.conflicts {
+        eprintln!("{}", conflict);
+    }
+    let aliases = clappers.get_single("aliases");
+    let aliases = if aliases.is_empty() { "assets/aliases.csv".to_owned() } else { aliases };
+    if let Err(e) = reg.load_aliases(&aliases) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let rulesets = Ruleset::parse_list(&clappers.get_single("rulesets")).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let battle = clappers.get_single("battle");
+    if !battle.is_empty() {
+        if let Err(e) = deck_battle(&reg, &battle) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let matchup = clappers.get_single("matchup");
+    if !matchup.is_empty() {
+        let games = clappers.get_single("games").parse().unwrap_or(1000);
+        let mut options = match clappers.get_single("width").parse() {
+            Ok(width) => MatchupOptions::until_width(games, width),
+            Err(_) => MatchupOptions::games(games),
+        };
+        options.seed = clappers.get_single("seed").parse().ok();
+        if let Err(e) = deck_matchup(&reg, &matchup, &rulesets, &options) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let outcomes = clappers.get_single("outcomes");
+    if !outcomes.is_empty() {
+        let max_states = clappers.get_single("max-states").parse().unwrap_or(MAX_STATES);
+        if let Err(e) = deck_outcomes(&reg, &outcomes, &rulesets, max_states) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let element_str = clappers.get_single("element");
     if !element_str.is_empty() {
         element = match element_str.as_ref() {
@@ -112,15 +252,18 @@ fn main() {
     train = clappers.get_single("train").parse().unwrap_or(train);
     lines = clappers.get_single("lines").parse().unwrap_or(lines);
 
-    println!("Element: {}\nMana: {}\nTrain: {}\nLines: {}", element_str, mana, train, lines);
+    let mut training = Training::new(train);
+    training.threads = clappers.get_single("threads").parse().unwrap_or(training.threads);
+    training.seed = clappers.get_single("seed").parse().ok();
+
+    println!("Element: {}\nMana: {}\nTrain: {}\nThreads: {}\nLines: {}\nRulesets: {:?}", element_str, mana, train, training.threads, lines, rulesets);
 
-    let reg = Registry::from("assets/new_cards.csv");
     if element == Element::Neutral {
-        super_tournament(&reg, mana, train, lines);
+        super_tournament(&reg, mana, &training, lines, &rulesets);
         return;
     }
-    tournament(&reg, element, mana, train, lines);
+    tournament(&reg, element, mana, &training, lines, &rulesets);
 
-    // let reg = Registry::from("assets/new_cards.csv");
+    // let reg = load_cards("assets/new_cards.csv");
     // tournament(&reg, element, mana, train, lines);
 }
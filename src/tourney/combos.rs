@@ -1,11 +1,8 @@
-use std::ops::Deref;
-
-use crate::battles::battle::Battle;
-use crate::gamedata::registry::Registry;
-use crate::cardparse::enums::{Element, Role, Ability};
+use crate::gamedata::{deck::{Deck, DeckRules}, registry::Registry};
+use crate::battles::ruleset::Ruleset;
+use crate::cardparse::enums::{Element, Role};
 use itertools::Itertools;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{rngs::StdRng, SeedableRng};
 
 use super::elo::Elo;
 
@@ -36,9 +33,10 @@ pub fn elo_combos(reg: &Registry, elem: Element, mana_cost: i32) -> Vec<Elo> {
         for combo in elem_mon.iter().combinations(5) {
             let val = combo.iter().fold(summ.1.mana_cost, |acc, (_, card)| acc + card.mana_cost);
             if val == mana_cost {
-                let mut res = vec![summ.0];
-                res.extend(combo.iter().map(|(name, _)| name));
-                valid.push(Elo::new(res));
+                let monsters = combo.iter().map(|(_, card)| (*card).clone()).collect();
+                if let Ok(deck) = Deck::from_cards(summ.1.clone(), monsters, DeckRules::mana(mana_cost)) {
+                    valid.push(Elo::new(deck));
+                }
             }
         }
     }
@@ -61,7 +59,7 @@ pub fn elo_combos(reg: &Registry, elem: Element, mana_cost: i32) -> Vec<Elo> {
 //     }
 // }
 
-// pub fn deck_combos<'a>(reg: &'a Registry, elem: Element, mana_cost: i32, collector: &'a mut Vec<Elo<'a>>) {
+// pub fn deck_combos<'a>(reg: &'a Registry, elem: Element, mana_cost: i32, collector: &'a mut Vec<Elo>) {
 //     let elem_mon = reg.filter(|card| (card.element == elem || card.element == Element::Neutral) && card.role == Role::Monster);
 //     let elem_summ = reg.filter(|card| (card.element == elem || card.element == Element::Neutral) && card.role == Role::Summoner);
 
@@ -85,25 +83,59 @@ pub fn elo_combos(reg: &Registry, elem: Element, mana_cost: i32) -> Vec<Elo> {
 
 // pub fn into_pairs(combos: Vec<Vec<&String>>) -> impl Iterator<Item = (Vec<&String>, Vec<&STring>)
 
-pub fn battle_in_pairs(elos: &mut [Elo], reg: &Registry) {
-    elos.iter_mut().fold(None, |acc, elem| {
-        match acc {
-            None => Some(elem),
-            Some(prev) => {
-                prev.battle(elem, reg);
-                None
-            }
+/// How training passes are run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Training {
+    /// Number of battle_in_pairs_parallel passes
+    pub passes: i32,
+    /// Worker threads battling pairs within a pass
+    pub threads: usize,
+    /// Seeds every battle so the Elo table can be reproduced, whatever the thread count
+    pub seed: Option<u64>,
+}
+
+impl Training {
+    /// One worker per available core, unseeded
+    pub fn new(passes: i32) -> Self {
+        Self {
+            passes,
+            threads: std::thread::available_parallelism().map_or(1, |x| x.get()),
+            seed: None,
+        }
+    }
+
+    /// Rng for a pair's battle, depends only on the seed, pass and pair so threads can't change it
+    fn pair_rng(&self, pass: i32, pair: usize) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ ((pass as u64) << 32) ^ pair as u64),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+/// Battles each elo with its neighbour, with the pairs split between worker threads
+pub fn battle_in_pairs_parallel(elos: &mut [Elo], rulesets: &[Ruleset], config: &Training, pass: i32) {
+    let mut pairs: Vec<(usize, &mut [Elo])> = elos.chunks_exact_mut(2).enumerate().collect();
+    let per_thread = pairs.len().div_ceil(config.threads.max(1)).max(1);
+    std::thread::scope(|scope| {
+        for group in pairs.chunks_mut(per_thread) {
+            scope.spawn(move || {
+                for (i, pair) in group.iter_mut() {
+                    let [home, oppo] = pair else { unreachable!("chunks are pairs") };
+                    home.battle_with_rng(oppo, rulesets, config.pair_rng(pass, *i));
+                }
+            });
         }
     });
 }
 
-pub fn tournament(reg: &Registry, elem: Element, mana_cost: i32, train: i32, lines: usize) {
+pub fn tournament(reg: &Registry, elem: Element, mana_cost: i32, config: &Training, lines: usize, rulesets: &[Ruleset]) {
     let mut elos = elo_combos(reg, elem, mana_cost);
 
-    training(reg, &mut elos, train, lines);
+    training(&mut elos, config, lines, rulesets);
 }
 
-pub fn super_tournament(reg: &Registry, mana_cost: i32, train: i32, lines: usize) {
+pub fn super_tournament(reg: &Registry, mana_cost: i32, config: &Training, lines: usize, rulesets: &[Ruleset]) {
     let mut elos = Vec::new();
 
     for elem in [Element::Fire, Element::Water, Element::Earth, Element::Life, Element::Death] {
@@ -111,7 +143,7 @@ pub fn super_tournament(reg: &Registry, mana_cost: i32, train: i32, lines: usize
     }
     let mut elos = elos.into_iter().flatten().collect();
 
-    training(reg, &mut elos, train, lines);
+    training(&mut elos, config, lines, rulesets);
 }
 
 pub fn cut_lt(elos: &mut Vec<Elo>, cutoff: f32) {
@@ -120,9 +152,9 @@ pub fn cut_lt(elos: &mut Vec<Elo>, cutoff: f32) {
     }
 }
 
-pub fn training(reg: &Registry, elos: &mut Vec<Elo>, train: i32, lines: usize) {
-    for _ in 0..train {
-        battle_in_pairs(elos, reg);
+pub fn training(elos: &mut Vec<Elo>, config: &Training, lines: usize, rulesets: &[Ruleset]) {
+    for pass in 0..config.passes {
+        battle_in_pairs_parallel(elos, rulesets, config, pass);
         elos.sort();
         cut_lt(elos, 1000.0);
     }
@@ -137,6 +169,8 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    #[ignore = "slow, prints every combo"]
     fn test_combos() {
         let reg = Registry::from("assets/new_cards.csv");
         
@@ -147,9 +181,40 @@ mod tests {
         }
     }
 
+    #[test]
+    #[ignore = "slow, plays a whole tournament"]
     fn test_tourney() {
         let reg = Registry::from("assets/new_cards.csv");
-        tournament(&reg, Element::Death, 16, 10, usize::MAX);
+        tournament(&reg, Element::Death, 16, &Training::new(10), usize::MAX, &[]);
+    }
+
+    #[test]
+    fn test_parallel_training() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let table = |threads| {
+            let mut elos = elo_combos(&reg, Element::Fire, 14);
+            training(&mut elos, &Training { passes: 4, threads, seed: Some(7) }, 0, &[]);
+            elos.iter().map(|x| x.to_string()).collect::<Vec<_>>()
+        };
+        let single = table(1);
+        assert!(single.len() > 2);
+        assert_eq!(table(3), single);
+        assert_eq!(table(8), single);
+    }
+
+    #[test]
+    fn test_seeded_training_across_registries() {
+        // each registry's maps hash differently, like separate runs of the program
+        let table = || {
+            let reg = Registry::from("assets/new_cards.csv");
+            let mut elos = elo_combos(&reg, Element::Fire, 14);
+            training(&mut elos, &Training { passes: 4, threads: 2, seed: Some(7) }, 0, &[]);
+            elos.iter().map(|x| x.to_string()).collect::<Vec<_>>()
+        };
+        let first = table();
+        for _ in 0..3 {
+            assert_eq!(table(), first);
+        }
     }
 }
 
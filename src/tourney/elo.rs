@@ -1,29 +1,34 @@
-use crate::{gamedata::registry::Registry, battles::battle::Battle, cardparse::enums::Outcome};
+use crate::{gamedata::deck::Deck, battles::{battle::Battle, ruleset::Ruleset}, cardparse::enums::Outcome};
+use rand::{rngs::StdRng, SeedableRng};
 use std::{fmt::Display, cmp::Ordering};
 
-/// Wraps a Vec<&str> deck, keeps track of deck ELO
+/// Wraps a deck, keeps track of deck ELO
 #[derive(Debug)]
-pub struct Elo<'a> {
-    pub deck: Vec<&'a str>,
+pub struct Elo {
+    pub deck: Deck,
     pub elo: f32,
 }
 
-impl<'a> Elo<'a> {
-    pub fn new(deck: Vec<&'a str>) -> Self {
+impl Elo {
+    pub fn new(deck: Deck) -> Self {
         Self {
             deck,
             elo: 1000.0,
         }
     }
 
-    pub fn battle(&mut self, other: &mut Self, reg: &Registry) {
-        let mut battle = Battle::new(reg, &self.deck, &other.deck);
+    pub fn battle(&mut self, other: &mut Self, rulesets: &[Ruleset]) {
+        self.battle_with_rng(other, rulesets, StdRng::from_entropy());
+    }
+
+    pub fn battle_with_rng(&mut self, other: &mut Self, rulesets: &[Ruleset], rng: StdRng) {
+        let mut battle = Battle::from_rulesets(&self.deck, &other.deck, rulesets, rng);
         let outcome = battle.game();
         self.outcome_of_battle(other, outcome);
     }
 
     pub fn prob_win(&self, other: &Self) -> f32 {
-        let step1 = other.elo as f32 - self.elo as f32;
+        let step1 = other.elo - self.elo;
         let step2 = step1 / 400.0;
         let step3 = f32::powf(10.0, step2);
         1.0 / (1.0 + step3)
@@ -44,40 +49,43 @@ impl<'a> Elo<'a> {
     }
 }
 
-impl<'a> Display for Elo<'a> {
+impl Display for Elo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({}) {:?}", self.elo, self.deck)
+        write!(f, "({}) {}", self.elo, self.deck)
     }
 }
 
-impl<'a> Ord for Elo<'a> {
+impl Ord for Elo {
     fn cmp(&self, other: &Self) -> Ordering {
         other.elo.total_cmp(&self.elo)
     }
 }
 
-impl<'a> PartialOrd for Elo<'a> {
+impl PartialOrd for Elo {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<'a> PartialEq for Elo<'a> {
+impl PartialEq for Elo {
     fn eq(&self, other: &Self) -> bool {
         self.elo == other.elo
     }
 }
 
-impl<'a> Eq for Elo<'a> {}
+impl Eq for Elo {}
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gamedata::registry::Registry;
+
     #[test]
     fn elo_test() {
-        let mut home = Elo::new(Vec::new());
-        let mut other = Elo::new(Vec::new());
+        let reg = Registry::from("assets/new_cards.csv");
+        let mut home = Elo::new(Deck::new(&reg, &["Pyre"]).unwrap());
+        let mut other = Elo::new(Deck::new(&reg, &["Tarsa"]).unwrap());
         home.elo = 1100.0;
         other.elo = 900.0;
         assert_eq!(home.prob_win(&other), 0.7597469);
@@ -85,8 +93,9 @@ mod tests {
 
     #[test]
     fn elo_battle_test() {
-        let mut home = Elo::new(Vec::new());
-        let mut other = Elo::new(Vec::new());
+        let reg = Registry::from("assets/new_cards.csv");
+        let mut home = Elo::new(Deck::new(&reg, &["Pyre"]).unwrap());
+        let mut other = Elo::new(Deck::new(&reg, &["Tarsa"]).unwrap());
         home.elo = 1656.0;
         other.elo = 1763.0;
         home.outcome_of_battle(&mut other, Outcome::Win);
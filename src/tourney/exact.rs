@@ -0,0 +1,259 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Display};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{battles::{battle::{Battle, BattleState}, chance::Chance, roundrobiniter::MAX_TURNS, ruleset::Ruleset}, cardparse::enums::Outcome, gamedata::deck::Deck};
+
+/// Default cap on distinct states, enough for small battles
+pub const MAX_STATES: usize = 200_000;
+
+/// Why a battle couldn't be enumerated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExactError {
+    /// The battle branches into more distinct states than the cap
+    TooManyStates { cap: usize },
+}
+
+impl Display for ExactError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExactError::TooManyStates { cap } => write!(f, "battle has more than {} distinct states, try smaller decks or a higher cap", cap),
+        }
+    }
+}
+
+impl std::error::Error for ExactError {}
+
+/// A chance event met while replaying a step
+#[derive(Debug, Clone, Copy)]
+enum Point {
+    /// Comes out true with this probability, choice 0 is true
+    Roll(f64),
+    /// Each of this many choices is equally likely
+    Pick(usize),
+}
+
+impl Point {
+    fn options(&self) -> usize {
+        match self {
+            Point::Roll(_) => 2,
+            Point::Pick(n) => *n,
+        }
+    }
+
+    fn prob(&self, choice: usize) -> f64 {
+        match *self {
+            Point::Roll(p) if choice == 0 => p,
+            Point::Roll(p) => 1.0 - p,
+            Point::Pick(n) => 1.0 / n as f64,
+        }
+    }
+}
+
+/// Chance that follows a path of choices, taking the 1st choice at points past its end
+/// Replaying a step once per path visits every way it can play out
+#[derive(Debug, Default)]
+pub struct Script {
+    path: Vec<(Point, usize)>,
+    pos: usize,
+}
+
+impl Script {
+    fn choose(&mut self, point: Point) -> usize {
+        if self.pos == self.path.len() {
+            self.path.push((point, 0));
+        }
+        let choice = self.path[self.pos].1;
+        self.pos += 1;
+        choice
+    }
+
+    /// Probability of the path taken since the last next_path
+    fn prob(&self) -> f64 {
+        self.path[..self.pos].iter().map(|(point, choice)| point.prob(*choice)).product()
+    }
+
+    /// Moves to the next path not yet taken, false once they all have been
+    fn next_path(&mut self) -> bool {
+        self.path.truncate(self.pos);
+        self.pos = 0;
+        while let Some((point, choice)) = self.path.last_mut() {
+            if *choice + 1 < point.options() {
+                *choice += 1;
+                return true;
+            }
+            self.path.pop();
+        }
+        false
+    }
+}
+
+/// Certain rolls and single picks don't branch
+impl Chance for Script {
+    fn roll(&mut self, p: f32) -> bool {
+        if p <= 0.0 {
+            return false;
+        }
+        if p >= 1.0 {
+            return true;
+        }
+        self.choose(Point::Roll(p as f64)) == 0
+    }
+
+    fn pick(&mut self, n: usize) -> usize {
+        if n == 1 {
+            return 0;
+        }
+        self.choose(Point::Pick(n))
+    }
+}
+
+/// Exact chances of each outcome from the home deck's side, found by following every chance event
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExactOutcomes {
+    pub win: f64,
+    pub draw: f64,
+    pub lose: f64,
+    /// Distinct states expanded, after merging identical ones
+    pub states: usize,
+}
+
+impl ExactOutcomes {
+    /// Enumerates a battle between home and oppo, giving up past max_states
+    pub fn evaluate(home: &Deck, oppo: &Deck, rulesets: &[Ruleset], max_states: usize) -> Result<Self, ExactError> {
+        let mut battle = Battle::from_rulesets(home, oppo, rulesets, StdRng::seed_from_u64(0));
+        Self::of_battle(&mut battle, max_states)
+    }
+
+    /// Enumerates battle from where it is, it's left there afterwards
+    pub fn of_battle(battle: &mut Battle, max_states: usize) -> Result<Self, ExactError> {
+        let start = battle.state();
+        let events = battle.battledata.events.take();
+        let result = Self::enumerate(battle, &start, max_states);
+        battle.set_state(&start);
+        battle.battledata.events = events;
+        result
+    }
+
+    /// States are expanded in order of how far into the battle they are, so every path to a state is merged before it's expanded
+    fn enumerate(battle: &mut Battle, start: &BattleState, max_states: usize) -> Result<Self, ExactError> {
+        let mut outcomes = Self::default();
+        let mut frontier: BTreeMap<(u32, usize), HashMap<BattleState, f64>> = BTreeMap::new();
+        frontier.entry(progress(start)).or_default().insert(start.clone(), 1.0);
+
+        while let Some((_, states)) = frontier.pop_first() {
+            for (state, prob) in states {
+                outcomes.states += 1;
+                if outcomes.states > max_states {
+                    return Err(ExactError::TooManyStates { cap: max_states });
+                }
+                let mut script = Script::default();
+                loop {
+                    battle.set_state(&state);
+                    let outcome = battle.step(&mut script);
+                    let path_prob = prob * script.prob();
+                    match outcome {
+                        Some(outcome) => outcomes.add(outcome, path_prob),
+                        None => {
+                            let next = battle.state();
+                            *frontier.entry(progress(&next)).or_default().entry(next).or_default() += path_prob;
+                        },
+                    }
+                    if !script.next_path() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+
+    fn add(&mut self, outcome: Outcome, prob: f64) {
+        match outcome {
+            Outcome::Win => self.win += prob,
+            Outcome::Draw => self.draw += prob,
+            Outcome::Lose => self.lose += prob,
+        }
+    }
+
+    pub fn get(&self, outcome: Outcome) -> f64 {
+        match outcome {
+            Outcome::Win => self.win,
+            Outcome::Draw => self.draw,
+            Outcome::Lose => self.lose,
+        }
+    }
+}
+
+/// Goes up with every step: the round, then turns taken, with a finished round after all of its turns
+fn progress(state: &BattleState) -> (u32, usize) {
+    let phase = match state.in_round {
+        true => MAX_TURNS - state.queue.len(),
+        false => MAX_TURNS + 1,
+    };
+    (state.round_num, phase)
+}
+
+impl Display for ExactOutcomes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "States: {}", self.states)?;
+        write!(f, "Win: {:.4}%  Draw: {:.4}%  Loss: {:.4}%", self.win * 100.0, self.draw * 100.0, self.lose * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gamedata::{deck::DeckRules, registry::Registry}, tourney::matchup::{Matchup, MatchupOptions}};
+
+    #[test]
+    fn test_script() {
+        // a roll then a pick of 3 on either branch gives 6 paths
+        let mut script = Script::default();
+        let mut total = 0.0;
+        let mut paths = 0;
+        loop {
+            script.roll(0.25);
+            script.pick(3);
+            assert!(!script.roll(0.0) && script.roll(1.0) && script.pick(1) == 0);
+            total += script.prob();
+            paths += 1;
+            if !script.next_path() {
+                break;
+            }
+        }
+        assert_eq!(paths, 6);
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_exact_outcomes() {
+        let reg = Registry::from("assets/new_cards.csv");
+        let deck = |cards: &[&str]| Deck::with_rules(&reg, cards, DeckRules::sandbox()).unwrap();
+        let home = deck(&["Tarsa", "Living Lava", "Serpentine Spy"]);
+        let oppo = deck(&["Kelya Frendul", "Sniping Narwhal", "Ice Pixie"]);
+
+        let exact = ExactOutcomes::evaluate(&home, &oppo, &[], MAX_STATES).unwrap();
+        assert!((exact.win + exact.draw + exact.lose - 1.0).abs() < 1e-9);
+        assert!(exact.win > 0.0 && exact.lose > 0.0);
+        // states merge in hash order so only the last bits of the sums can differ
+        let again = ExactOutcomes::evaluate(&home, &oppo, &[], MAX_STATES).unwrap();
+        assert_eq!(again.states, exact.states);
+        assert!((again.win - exact.win).abs() < 1e-12);
+        assert_eq!(ExactOutcomes::evaluate(&home, &oppo, &[], 10), Err(ExactError::TooManyStates { cap: 10 }));
+
+        // sampling lands inside a wide interval around the exact answer
+        let options = MatchupOptions { seed: Some(3), ..MatchupOptions::games(2000) };
+        let sampled = Matchup::evaluate(&home, &oppo, &[], &options);
+        let (low, high) = sampled.win_interval(3.3);
+        assert!(low <= exact.win && exact.win <= high, "{} outside {} - {}", exact.win, low, high);
+
+        // enumerating mid battle leaves it where it was
+        let mut battle = Battle::with_seed(&home, &oppo, 1);
+        battle.start_round();
+        battle.next_turn();
+        let state = battle.state();
+        ExactOutcomes::of_battle(&mut battle, MAX_STATES).unwrap();
+        assert_eq!(battle.state(), state);
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt::Display;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{battles::{battle::Battle, ruleset::Ruleset}, cardparse::enums::Outcome, gamedata::deck::Deck};
+
+/// z score for a 95% confidence interval
+pub const Z_95: f64 = 1.96;
+
+/// How many games a matchup plays and when it can stop early
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchupOptions {
+    /// Most games played
+    pub max_games: u32,
+    /// Games played before an early stop is considered
+    pub min_games: u32,
+    /// Stop once the win rate interval is at most this wide, None always plays max_games
+    pub ci_width: Option<f64>,
+    /// Seeds every game so the report can be reproduced
+    pub seed: Option<u64>,
+}
+
+impl MatchupOptions {
+    /// Plays exactly games games
+    pub fn games(games: u32) -> Self {
+        Self {
+            max_games: games,
+            min_games: games,
+            ci_width: None,
+            seed: None,
+        }
+    }
+
+    /// Plays up to max_games, stopping once the interval is narrower than width
+    pub fn until_width(max_games: u32, width: f64) -> Self {
+        Self {
+            max_games,
+            min_games: 30.min(max_games),
+            ci_width: Some(width),
+            seed: None,
+        }
+    }
+}
+
+/// Tally of games between two decks, from the home deck's side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Matchup {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Rounds played over every game
+    pub rounds: u64,
+}
+
+impl Matchup {
+    /// Plays home against oppo under the rulesets until options say to stop
+    pub fn evaluate(home: &Deck, oppo: &Deck, rulesets: &[Ruleset], options: &MatchupOptions) -> Self {
+        let mut matchup = Self::default();
+        while matchup.games() < options.max_games {
+            let rng = match options.seed {
+                Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(matchup.games() as u64)),
+                None => StdRng::from_entropy(),
+            };
+            let mut battle = Battle::from_rulesets(home, oppo, rulesets, rng);
+            let outcome = battle.game();
+            matchup.add(outcome, battle.round_num);
+            if matchup.narrow_enough(options) {
+                break;
+            }
+        }
+        matchup
+    }
+
+    pub fn add(&mut self, outcome: Outcome, rounds: u32) {
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Draw => self.draws += 1,
+            Outcome::Lose => self.losses += 1,
+        }
+        self.rounds += rounds as u64;
+    }
+
+    fn narrow_enough(&self, options: &MatchupOptions) -> bool {
+        let Some(width) = options.ci_width else {
+            return false;
+        };
+        let (low, high) = self.win_interval(Z_95);
+        self.games() >= options.min_games && high - low <= width
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    fn rate(&self, count: u32) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => count as f64 / games as f64,
+        }
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        self.rate(self.wins)
+    }
+
+    pub fn draw_rate(&self) -> f64 {
+        self.rate(self.draws)
+    }
+
+    pub fn loss_rate(&self) -> f64 {
+        self.rate(self.losses)
+    }
+
+    pub fn avg_rounds(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => self.rounds as f64 / games as f64,
+        }
+    }
+
+    /// Wilson score interval for the win rate, stays inside 0..=1 even with few games or a one sided matchup
+    pub fn win_interval(&self, z: f64) -> (f64, f64) {
+        let n = self.games() as f64;
+        if n == 0.0 {
+            return (0.0, 1.0);
+        }
+        let p = self.win_rate();
+        let z2 = z * z;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        ((center - margin).max(0.0), (center + margin).min(1.0))
+    }
+}
+
+impl Display for Matchup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (low, high) = self.win_interval(Z_95);
+        writeln!(f, "Games: {}", self.games())?;
+        writeln!(f, "Win: {:.1}%  Draw: {:.1}%  Loss: {:.1}%", self.win_rate() * 100.0, self.draw_rate() * 100.0, self.loss_rate() * 100.0)?;
+        writeln!(f, "Win rate 95% interval: {:.1}% - {:.1}%", low * 100.0, high * 100.0)?;
+        write!(f, "Average rounds: {:.2}", self.avg_rounds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamedata::{deck::DeckRules, registry::Registry};
+
+    #[test]
+    fn test_matchup() {
+        let empty = Matchup::default();
+        assert_eq!(empty.win_interval(Z_95), (0.0, 1.0));
+        let even = Matchup { wins: 50, draws: 0, losses: 50, rounds: 400 };
+        let (low, high) = even.win_interval(Z_95);
+        assert!((low - 0.4038).abs() < 1e-3 && (high - 0.5962).abs() < 1e-3);
+        assert_eq!(even.avg_rounds(), 4.0);
+
+        let reg = Registry::from("assets/new_cards.csv");
+        let home = Deck::new(&reg, &["Tarsa", "Living Lava", "Magma Troll"]).unwrap();
+        let oppo = Deck::with_rules(&reg, &["Kelya Frendul", "Serpent of Eld", "Feasting Seaweed"], DeckRules::sandbox()).unwrap();
+        let options = MatchupOptions { seed: Some(11), ..MatchupOptions::games(40) };
+        let matchup = Matchup::evaluate(&home, &oppo, &[], &options);
+        assert_eq!(matchup.games(), 40);
+        assert!(matchup.rounds >= 40);
+        assert_eq!(Matchup::evaluate(&home, &oppo, &[], &options), matchup);
+
+        let options = MatchupOptions { seed: Some(11), ..MatchupOptions::until_width(10_000, 0.3) };
+        let early = Matchup::evaluate(&home, &oppo, &[], &options);
+        assert!(early.games() >= 30 && early.games() < 10_000);
+        let (low, high) = early.win_interval(Z_95);
+        assert!(high - low <= 0.3);
+    }
+}
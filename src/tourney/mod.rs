@@ -1,2 +1,4 @@
 pub mod combos;
 pub mod elo;
+pub mod matchup;
+pub mod exact;